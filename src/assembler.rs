@@ -0,0 +1,593 @@
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+use rainbow_wrapper::wrapper::Wrapper;
+
+use crate::{ast::Program, deps::{hash, make_rule, DepFile}, diagnostic::{Diagnostic, Diagnostics, Severity}, file_system::{normalize, FileSystem, OsFileSystem}, lower::{emit, lower}, macros::expand_macros, parser::parse, preprocess::{preprocess, Defines}, source_map::SourceMap, symbols::{check, Declarations}, tokenizer::{tokenize, tokenize_file}, typecheck};
+
+// assembles RASM source into rainbow bytecode
+//
+// let bytes = Assembler::new().link_path("lib").define("SIZE", "16").assemble_str(src)?;
+pub struct Assembler {
+    pub(crate) link_paths: Vec<String>,
+    pub(crate) defines: Vec<(String, String)>,
+    pub(crate) runtime: HashSet<String>,
+    pub(crate) fs: Box<dyn FileSystem>,
+    force: bool,
+    strict: bool,
+    last_warnings: Diagnostics,         // the warnings of the last run that worked
+
+    // state of the current run
+    pub(crate) sources: SourceMap,
+    loaded: HashMap<String, usize>,     // ids of the files read so far
+    include_stack: Vec<Open>,           // files that are being assembled, outermost first
+    included: HashMap<String, bool>,    // files that were already assembled or up to date, and whether that worked
+    deps: HashMap<String, Vec<String>>, // the files every assembled file was made from, including itself
+    indexes: HashMap<String, Vec<String>>, // the files below every folder searched so far
+    dependencies: Vec<String>,          // the files the last input was made from
+    warnings: Vec<Diagnostic>,
+}
+
+// a file that is being assembled
+struct Open {
+    path: String,
+    deps: Vec<String>, // the file itself and every file it used so far
+}
+
+// why an included `.rasm` file could not be used
+pub(crate) enum IncludeError {
+    CYCLE(Vec<String>), // the include chain, starting and ending with the same file
+    FAILED(Vec<Diagnostic>),
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Assembler::new()
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Assembler {
+        Assembler {
+            link_paths: Vec::new(),
+            defines: Vec::new(),
+            runtime: HashSet::new(),
+            fs: Box::new(OsFileSystem),
+            force: false,
+            strict: false,
+            last_warnings: Diagnostics::new(Vec::new(), SourceMap::new()),
+            sources: SourceMap::new(),
+            loaded: HashMap::new(),
+            include_stack: Vec::new(),
+            included: HashMap::new(),
+            deps: HashMap::new(),
+            indexes: HashMap::new(),
+            dependencies: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    // adds a folder to search for included files in, after the ones added before it
+    pub fn link_path(mut self, path: impl Into<String>) -> Assembler {
+        add_link_path(path.into(), &mut self.link_paths);
+        self
+    }
+
+    // makes `name` stand for `value` in instruction arguments and `.if` conditions
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Assembler {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    // marks `name` as provided by the runtime, so `.if` chains using it are left for the runtime to decide
+    pub fn runtime(mut self, name: impl Into<String>) -> Assembler {
+        self.runtime.insert(name.into());
+        self
+    }
+
+    pub fn file_system(mut self, fs: impl FileSystem + 'static) -> Assembler {
+        self.fs = Box::new(fs);
+        self
+    }
+
+    // assembles every file again, even when the `.d` file next to its output says nothing changed
+    pub fn force(mut self, force: bool) -> Assembler {
+        self.force = force;
+        self
+    }
+
+    // makes the warnings of the type checker errors
+    pub fn strict(mut self, strict: bool) -> Assembler {
+        self.strict = strict;
+        self
+    }
+
+    // assembles source that does not come from a file, includes are looked up in the working directory and then the link paths
+    pub fn assemble_str(&mut self, src: &str) -> Result<Vec<u8>, Diagnostics> {
+        return self.run(|asm| {
+            let file_id = asm.sources.add("<input>", src.to_string());
+
+            asm.include_stack.push(Open { path: String::from("<input>"), deps: Vec::new() });
+            let res = asm.assemble_source(file_id).map(|(bytes, _)| bytes);
+            asm.dependencies = asm.include_stack.pop().unwrap().deps;
+
+            return res;
+        });
+    }
+
+    pub fn assemble_file(&mut self, path: &str) -> Result<Vec<u8>, Diagnostics> {
+        return self.run(|asm| {
+            let file_id = asm.load(path)?;
+
+            asm.include_stack.push(Open { path: path.to_string(), deps: vec![path.to_string()] });
+            let res = asm.assemble_source(file_id).map(|(bytes, _)| bytes);
+            asm.dependencies = asm.include_stack.pop().unwrap().deps;
+
+            return res;
+        });
+    }
+
+    // the tree of a file after macros were expanded and `.if` chains decided while assembling were removed, for tools working on RASM
+    // included files are not assembled
+    pub fn parse_file(&mut self, path: &str) -> Result<Program, Diagnostics> {
+        return self.run(|asm| {
+            let file_id = asm.load(path)?;
+
+            asm.include_stack.push(Open { path: path.to_string(), deps: vec![path.to_string()] });
+            let res = asm.parse_source(file_id);
+            asm.include_stack.pop();
+
+            return res;
+        });
+    }
+
+    pub fn parse_str(&mut self, src: &str) -> Result<Program, Diagnostics> {
+        return self.run(|asm| {
+            let file_id = asm.sources.add("<input>", src.to_string());
+
+            asm.include_stack.push(Open { path: String::from("<input>"), deps: Vec::new() });
+            let res = asm.parse_source(file_id);
+            asm.include_stack.pop();
+
+            return res;
+        });
+    }
+
+    // whether `output` was assembled from `path` and nothing it was made from changed since
+    pub fn is_up_to_date(&mut self, path: &str, output: &str) -> bool {
+        self.reset();
+
+        let deps = if self.force { None } else { self.up_to_date(path, output) };
+        let res = deps.is_some();
+
+        self.dependencies = deps.unwrap_or_default();
+        self.sources = SourceMap::new();
+
+        return res;
+    }
+
+    // assembles `path` into `output`, and writes the `.d` file used by `is_up_to_date` next to it
+    // included files are only assembled again when they changed, `path` itself always is
+    // returns the number of bytes written
+    pub fn build(&mut self, path: &str, output: &str) -> Result<usize, Diagnostics> {
+        let res = self.run(|asm| asm.build_file(path, output, false));
+
+        self.dependencies = self.deps.get(&normalize(path)).cloned().unwrap_or_default();
+
+        return res;
+    }
+
+    // the files the last input was made from, itself included: every `.rasm` it read, the `.rbb` files of its includes,
+    // and the `.rbb` files and libraries it refers to that could be found
+    // when assembling failed, this is what was found before it failed
+    pub fn dependencies(&self) -> &Vec<String> {
+        &self.dependencies
+    }
+
+    // the warnings of the last run, when it worked, a failed run returns them along with its errors
+    pub fn warnings(&self) -> &Diagnostics {
+        &self.last_warnings
+    }
+
+    // `target: dependencies...`, the dependencies of the last input as a make rule
+    pub fn make_rule(&self, target: &str) -> String {
+        return make_rule(target, &self.dependencies);
+    }
+
+    // gives every run a fresh source map, and forgets what was read from the file system by the last one
+    fn run<T>(&mut self, f: impl FnOnce(&mut Assembler) -> Result<T, Vec<Diagnostic>>) -> Result<T, Diagnostics> {
+        self.reset();
+
+        let res = f(self);
+
+        let sources = std::mem::take(&mut self.sources);
+        let warnings = std::mem::take(&mut self.warnings);
+
+        match res {
+            Ok(res) => {
+                self.last_warnings = Diagnostics::new(Vec::new(), sources).with_warnings(warnings);
+                return Ok(res);
+            }
+            Err(errors) => {
+                self.last_warnings = Diagnostics::new(Vec::new(), SourceMap::new());
+                return Err(Diagnostics::new(errors, sources).with_warnings(warnings));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sources = SourceMap::new();
+        self.loaded.clear();
+        self.include_stack.clear();
+        self.included.clear();
+        self.deps.clear();
+        self.indexes.clear();
+        self.dependencies.clear();
+        self.warnings.clear();
+    }
+
+    // assembles an included `.rasm` file and writes the `.rbb` next to it, unless the `.rbb` is up to date
+    // every file is only assembled once per run, later includes reuse the `.rbb` that was written
+    pub(crate) fn assemble_include(&mut self, path: &str) -> Result<(), IncludeError> {
+        let key = normalize(path);
+
+        if let Some(start) = self.include_stack.iter().position(|open| normalize(&open.path) == key) {
+            let mut chain: Vec<String> = self.include_stack[start..].iter().map(|open| open.path.clone()).collect();
+            chain.push(path.to_string());
+
+            return Err(IncludeError::CYCLE(chain));
+        }
+
+        let rbb_file = path.split(".rasm").next().unwrap().to_string() + ".rbb";
+
+        let res = match self.included.get(&key) {
+            Some(true) => Ok(()),
+            // the errors were reported the first time
+            Some(false) => Err(IncludeError::FAILED(Vec::new())),
+            None => {
+                let res = self.build_file(path, &rbb_file, !self.force);
+                self.included.insert(key.clone(), res.is_ok());
+
+                res.map(|_| ()).map_err(IncludeError::FAILED)
+            }
+        };
+
+        // whatever the included file was made from, the file including it is made from too
+        let mut deps = self.deps.get(&key).cloned().unwrap_or_default();
+        if res.is_ok() {
+            deps.push(rbb_file);
+        }
+
+        for dep in deps {
+            self.add_dependency(dep);
+        }
+
+        return res;
+    }
+
+    // records that the file being assembled uses the file `.include` or `.extern` refers to with `name`, if it can be found
+    pub(crate) fn use_file(&mut self, name: &str) {
+        if let Ok(Some(path)) = self.find_include(name, self.current_file().as_deref()) {
+            self.add_dependency(path);
+        }
+    }
+
+    fn add_dependency(&mut self, path: String) {
+        if let Some(open) = self.include_stack.last_mut() {
+            if !open.deps.iter().any(|dep| normalize(dep) == normalize(&path)) {
+                open.deps.push(path);
+            }
+        }
+    }
+
+    // assembles `path` and writes it to `output` with its `.d` and `.decl` files, unless `check` is set and it is up to date
+    // returns the number of bytes written
+    fn build_file(&mut self, path: &str, output: &str, check: bool) -> Result<usize, Vec<Diagnostic>> {
+        if check {
+            if let Some(deps) = self.up_to_date(path, output) {
+                self.deps.insert(normalize(path), deps);
+                return Ok(0);
+            }
+        }
+
+        let file_id = self.load(path)?;
+
+        self.include_stack.push(Open { path: path.to_string(), deps: vec![path.to_string()] });
+        let res = self.assemble_source(file_id);
+        let open = self.include_stack.pop().unwrap();
+        self.deps.insert(normalize(path), open.deps.clone());
+
+        let (bytes, declarations) = res?;
+
+        if let Err(e) = self.fs.write(output, &bytes) {
+            return Err(vec![Diagnostic::new(file_id, 0, 0, 0, format!("failed to write `{output}`: {e}"))]);
+        }
+
+        let decl_path = output.to_string() + ".decl";
+        if let Err(e) = self.fs.write(&decl_path, declarations.render().as_bytes()) {
+            return Err(vec![Diagnostic::new(file_id, 0, 0, 0, format!("failed to write `{decl_path}`: {e}"))]);
+        }
+
+        let mut dep_file = DepFile { defines: self.defines_hash(), files: Vec::new() };
+        for dep in &open.deps {
+            dep_file.files.push((dep.clone(), self.hash_file(dep).unwrap_or_default()));
+        }
+
+        let dep_path = output.to_string() + ".d";
+        if let Err(e) = self.fs.write(&dep_path, dep_file.render(output).as_bytes()) {
+            return Err(vec![Diagnostic::new(file_id, 0, 0, 0, format!("failed to write `{dep_path}`: {e}"))]);
+        }
+
+        return Ok(bytes.len());
+    }
+
+    // the files `output` was made from, if it was made from `path` with the same defines and none of them changed since
+    fn up_to_date(&mut self, path: &str, output: &str) -> Option<Vec<String>> {
+        if !self.fs.exists(output) || !self.fs.exists(&(output.to_string() + ".decl")) {
+            return None;
+        }
+
+        let dep_file = DepFile::parse(&self.fs.read_to_string(&(output.to_string() + ".d")).ok()?)?;
+
+        if dep_file.defines != self.defines_hash() || !dep_file.files.iter().any(|(dep, _)| normalize(dep) == normalize(path)) {
+            return None;
+        }
+
+        for (dep, hash) in &dep_file.files {
+            if self.hash_file(dep) != Some(*hash) {
+                return None;
+            }
+        }
+
+        return Some(dep_file.files.into_iter().map(|(dep, _)| dep).collect());
+    }
+
+    // files that were read already are not read again
+    fn hash_file(&self, path: &str) -> Option<u64> {
+        if let Some(file) = self.loaded.get(&normalize(path)).and_then(|file_id| self.sources.get(*file_id)) {
+            return Some(hash(file.contents.as_bytes()));
+        }
+
+        return self.fs.read(path).ok().map(|bytes| hash(&bytes));
+    }
+
+    // the defines change what is assembled, so outputs made with other defines are not up to date
+    // neither are outputs made without `strict`, they could have warnings
+    fn defines_hash(&self) -> u64 {
+        let mut runtime: Vec<&String> = self.runtime.iter().collect();
+        runtime.sort();
+
+        let mut s = String::new();
+        for (name, value) in &self.defines {
+            s += &format!("-D{name}={value}\n");
+        }
+        for name in runtime {
+            s += &format!("-R{name}\n");
+        }
+        if self.strict {
+            s += "--strict\n";
+        }
+
+        return hash(s.as_bytes());
+    }
+
+    // reads a file into the source map, every file is only read once per run
+    pub(crate) fn load(&mut self, path: &str) -> Result<usize, Diagnostic> {
+        let key = normalize(path);
+
+        if let Some(file_id) = self.loaded.get(&key) {
+            return Ok(*file_id);
+        }
+
+        match self.fs.read_to_string(path) {
+            Ok(contents) => {
+                let file_id = self.sources.add(path, contents);
+                self.loaded.insert(key, file_id);
+
+                return Ok(file_id);
+            }
+            Err(e) => return Err(Diagnostic::new(self.sources.add(path, String::new()), 0, 0, 0, format!("failed to read `{path}`: {e}"))),
+        }
+    }
+
+    // the bytecode of a file and the names it declares for the files including it
+    fn assemble_source(&mut self, file_id: usize) -> Result<(Vec<u8>, Declarations), Vec<Diagnostic>> {
+        let program = self.parse_source(file_id)?;
+
+        // included files are assembled while lowering, so their declarations exist when checking
+        let mut wrapper = Wrapper::new();
+        let lowered = lower(&program, &mut wrapper, self);
+
+        match (lowered, check(&program, self)) {
+            (Ok(exprs), Ok(())) => {
+                let mut warnings = typecheck::check(&program);
+
+                if self.strict && warnings.len() > 0 {
+                    return Err(warnings.into_iter().map(|w| Diagnostic { severity: Severity::ERROR, ..w }).collect());
+                }
+                self.warnings.append(&mut warnings);
+
+                wrapper.push(emit(&exprs));
+                return Ok((wrapper.emit(), Declarations::of(&program)));
+            }
+            (Err(mut errors), Err(mut e)) => {
+                errors.append(&mut e);
+                return Err(errors);
+            }
+            (Err(errors), _) | (_, Err(errors)) => return Err(errors),
+        }
+    }
+
+    // the declarations next to the `.rbb` an `.include` refers to, `None` if it or they can not be found
+    pub(crate) fn declarations(&mut self, file: &str, quoted: bool) -> Option<Declarations> {
+        let from = self.current_file();
+
+        let rbb_file = if quoted && file.ends_with(".rasm") {
+            let path = self.find_include(file, from.as_deref()).ok()??;
+            path.split(".rasm").next().unwrap().to_string() + ".rbb"
+        } else if quoted && file.ends_with(".rbb") {
+            self.find_include(file, from.as_deref()).ok()??
+        } else {
+            self.find_include(&(file.to_string() + ".rbb"), from.as_deref()).ok()??
+        };
+
+        let s = self.fs.read_to_string(&(rbb_file + ".decl")).ok()?;
+        return Some(Declarations::parse(&s));
+    }
+
+    fn parse_source(&mut self, file_id: usize) -> Result<Program, Vec<Diagnostic>> {
+        let (tokens, mut errors) = tokenize_file(&self.sources.get(file_id).unwrap().contents, file_id);
+
+        let tokens = match expand_macros(tokens, self) {
+            Ok(tokens) => tokens,
+            Err(mut e) => {
+                errors.append(&mut e);
+                Vec::new()
+            }
+        };
+
+        let tokens = match self.defines().and_then(|defines| preprocess(tokens, defines)) {
+            Ok(tokens) => tokens,
+            Err(mut e) => {
+                errors.append(&mut e);
+                Vec::new()
+            }
+        };
+
+        match parse(tokens) {
+            Ok(program) if errors.len() == 0 => return Ok(program),
+            Ok(_) => return Err(errors),
+            Err(mut e) => {
+                errors.append(&mut e);
+                return Err(errors);
+            }
+        }
+    }
+
+    // tokenizes the values given to `define`
+    fn defines(&mut self) -> Result<Defines, Vec<Diagnostic>> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut defines = Defines { values: HashMap::new(), runtime: self.runtime.clone() };
+
+        for (name, value) in &self.defines {
+            let file_id = self.sources.add(&format!("<define {name}>"), value.clone());
+
+            match tokenize(value.clone(), 1, file_id) {
+                Ok(line) if line.len() > 0 => {
+                    defines.values.insert(name.clone(), line);
+                }
+                Ok(line) => errors.push(line.error(format!("`{name}` is defined without a value"))),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.len() > 0 {
+            return Err(errors);
+        }
+
+        return Ok(defines);
+    }
+
+    // the file whose includes are being resolved, `None` for source that does not come from a file
+    pub(crate) fn current_file(&self) -> Option<String> {
+        return self.include_stack.last().map(|open| open.path.clone());
+    }
+
+    // the folders searched for included files, in order: the folder of the including file, then the link paths
+    pub(crate) fn search_path(&self, from: Option<&str>) -> Vec<String> {
+        let mut res = vec![folder_of(from.unwrap_or(""))];
+
+        for path in &self.link_paths {
+            if !res.contains(path) {
+                res.push(path.clone());
+            }
+        }
+
+        return res;
+    }
+
+    // finds the file `.include "name"` refers to, `from` is the file containing the include
+    // the first folder of the search path with a match wins, a file right in the folder wins over ones in subfolders
+    pub(crate) fn find_include(&mut self, name: &str, from: Option<&str>) -> Result<Option<String>, String> {
+        // `/io.rasm` is absolute for `normalize` on every platform, not only where `Path` says so
+        if name.starts_with(['/', '\\']) || Path::new(name).is_absolute() {
+            return Ok(if self.fs.exists(name) { Some(name.to_string()) } else { None });
+        }
+
+        let wanted = components(name);
+
+        for folder in self.search_path(from) {
+            let path = match folder.as_str() {
+                "." => name.to_string(),
+                "/" => format!("/{name}"),
+                _ => format!("{folder}/{name}"),
+            };
+            if self.fs.exists(&path) {
+                return Ok(Some(path));
+            }
+
+            // `io.rasm` also finds `folder/std/io.rasm`, but not `folder/studio.rasm`
+            let base = components(&folder).len();
+            let found: Vec<&String> = self.index(&folder)?.iter().filter(|file| {
+                let parts = components(file);
+                return parts.len() > base && parts[base..].ends_with(&wanted);
+            }).collect();
+
+            match found.len() {
+                0 => {}
+                1 => return Ok(Some(found[0].trim_start_matches("./").to_string())),
+                _ => {
+                    let found: Vec<String> = found.iter().map(|f| format!("`{}`", f.trim_start_matches("./"))).collect();
+                    return Err(format!("ambiguous include `{name}`, it matches {} in `{folder}`", found.join(" and ")));
+                }
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // every file below `folder`, sorted so the search does not depend on the order the file system lists them in
+    // folders are only listed once per run
+    fn index(&mut self, folder: &str) -> Result<&Vec<String>, String> {
+        if !self.indexes.contains_key(folder) {
+            let mut files = Vec::new();
+
+            // link paths that do not exist are skipped
+            if self.fs.exists(folder) {
+                files = self.fs.files(folder).map_err(|e| format!("failed to search `{folder}`: {e}"))?;
+                files.sort();
+            }
+
+            self.indexes.insert(folder.to_string(), files);
+        }
+
+        return Ok(&self.indexes[folder]);
+    }
+}
+
+// this function shouldnt need to exist
+fn add_link_path(mut folder: String, link_paths: &mut Vec<String>) {
+    folder = folder.replace("\\", "/");
+    if folder.ends_with("/") && folder.len() > 1 {
+        folder = folder[..folder.len()-1].to_string();
+    }
+    if folder.len() == 0 {
+        folder = String::from(".");
+    }
+    if !link_paths.contains(&folder) {
+        link_paths.push(folder);
+    }
+}
+
+// the folder a file is in, `.` if the path has no folder
+fn folder_of(path: &str) -> String {
+    return match path.replace("\\", "/").rsplit_once('/') {
+        Some(("", _)) => String::from("/"),
+        Some((folder, _)) => folder.to_string(),
+        None => String::from("."),
+    };
+}
+
+// the parts of a path, `./a//b\c` gives `a b c`
+fn components(path: &str) -> Vec<String> {
+    return normalize(path).split('/').filter(|p| p.len() > 0 && *p != ".").map(|p| p.to_string()).collect();
+}
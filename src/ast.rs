@@ -0,0 +1,177 @@
+// the tree `parser::parse` builds from the tokens left after macros and defines were handled
+// it does not depend on how the source was split into lines, every node knows where it came from
+pub use crate::{condition::{Comparison, Condition}, data::DataValue, instruction::Instruction, number::Number, tokenizer::{SpannedToken, Token, Type}};
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Item {
+    FUNCTION(Function),
+    STRUCT(Struct),
+    EXTERN(Extern),
+    MODULE(Module),
+    IMPORT(Import),
+    DATA(Vec<Constant>),
+    STMT(Stmt), // instructions and blocks outside of functions
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Stmt {
+    INSTR(Instr),
+    LABEL(Name),
+    SCOPE(Vec<Stmt>),
+    IF(If),
+}
+
+// where a node came from, a node spanning more than one line points at its first line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub file_id: usize,
+    pub line: usize,      // 1 based
+    pub col_start: usize, // 0 based, inclusive
+    pub col_end: usize,   // 0 based, exclusive
+}
+
+impl Span {
+    pub fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.file_id, self.line, self.col_start, self.col_end, message)
+    }
+
+    pub fn warning(&self, message: impl Into<String>) -> Diagnostic {
+        self.error(message).warning()
+    }
+
+    // the span from the start of `self` to the end of `end`, if both are on the same line
+    pub fn to(&self, end: &Span) -> Span {
+        if end.file_id != self.file_id || end.line != self.line || end.col_end < self.col_start {
+            return *self;
+        }
+
+        return Span { col_end: end.col_end, ..*self };
+    }
+}
+
+impl SpannedToken {
+    pub fn span(&self) -> Span {
+        Span { file_id: self.file_id, line: self.line, col_start: self.col_start, col_end: self.col_end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name {
+    pub name: String,
+    pub span: Span,
+}
+
+// an argument of a function or a field of a struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub typ: Vec<Type>,
+    pub name: Name,
+}
+
+// `(return type) (name)((args)) { (body) }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub ret_type: Vec<Type>,
+    pub name: Name,
+    pub args: Vec<Field>,
+    pub body: Vec<Stmt>,
+}
+
+// `.struct (name) { (fields) }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: Name,
+    pub fields: Vec<Field>,
+}
+
+// `.extern (return type) (name)((arg types)) from "(file)"`, optionally followed by `as (access name)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extern {
+    pub ret_type: Vec<Type>,
+    pub name: Name,
+    pub arg_types: Vec<Vec<Type>>,
+    pub file: String,
+    pub file_span: Span,
+    pub access_name: Option<Name>,
+}
+
+// `.module (name) { (items) }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: Name,
+    pub items: Vec<Item>,
+}
+
+// `.include "(file)"`, or `.include (name)` for `(name).rbb`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub file: String,
+    pub quoted: bool,
+    pub span: Span, // of the file
+}
+
+// `(name) (type) (value)` in the `.data` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub name: Name,
+    pub typ: Vec<Type>,
+    pub value: DataValue,
+    pub value_span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instr {
+    pub instruction: Instruction,
+    pub name: Name,
+    pub operands: Vec<Operand>,
+}
+
+impl Instr {
+    // from the name of the instruction to its last operand
+    pub fn span(&self) -> Span {
+        match self.operands.last() {
+            Some(last) => self.name.span.to(&last.span),
+            None => self.name.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operand {
+    pub value: Value,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Value {
+    NUMBER(Number),
+    STRING(String),
+    NAME(String),   // a function, module path, constant, `@` dynamic variable or condition like `>=`
+    VAR(String),    // `$name`, or `$name.field`
+    TYPE(Vec<Type>),
+    LABEL(String),  // `:name`
+}
+
+// `.if (condition)`, any number of `.elseif (condition)`, an optional `.else` and `.end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct If {
+    pub branches: Vec<Branch>,
+    pub otherwise: Option<Vec<Stmt>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub condition: Condition,
+    pub body: Vec<Stmt>,
+    pub span: Span, // of the directive
+}
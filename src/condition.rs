@@ -0,0 +1,402 @@
+use std::cmp::Ordering;
+
+use crate::{diagnostic::Diagnostic, number::Number, preprocess::Defines, tokenizer::{Line, SpannedToken, Token}};
+
+const MAX_DEPTH: usize = 64;
+
+// the condition of an `.if` or `.elseif`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    VALUE(SpannedToken),   // a number, string or name
+    DEFINED(SpannedToken), // `defined(NAME)`
+    NOT(Box<Condition>),
+    AND(Box<Condition>, Box<Condition>),
+    OR(Box<Condition>, Box<Condition>),
+    COMPARE(Box<Condition>, Comparison, Box<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    EQ,
+    NE,
+    LT,
+    LE,
+    GT,
+    GE,
+}
+
+impl Comparison {
+    fn from_str(s: &str) -> Option<Comparison> {
+        match s {
+            "==" => Some(Comparison::EQ),
+            "!=" => Some(Comparison::NE),
+            "<" => Some(Comparison::LT),
+            "<=" => Some(Comparison::LE),
+            ">" => Some(Comparison::GT),
+            ">=" => Some(Comparison::GE),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::EQ => "==",
+            Comparison::NE => "!=",
+            Comparison::LT => "<",
+            Comparison::LE => "<=",
+            Comparison::GT => ">",
+            Comparison::GE => ">=",
+        }
+    }
+
+    fn invert(&self) -> Comparison {
+        match self {
+            Comparison::EQ => Comparison::NE,
+            Comparison::NE => Comparison::EQ,
+            Comparison::LT => Comparison::GE,
+            Comparison::LE => Comparison::GT,
+            Comparison::GT => Comparison::LE,
+            Comparison::GE => Comparison::LT,
+        }
+    }
+
+    fn matches(&self, ordering: Option<Ordering>) -> bool {
+        match self {
+            Comparison::EQ => ordering == Some(Ordering::Equal),
+            Comparison::NE => ordering != Some(Ordering::Equal),
+            Comparison::LT => ordering == Some(Ordering::Less),
+            Comparison::LE => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Comparison::GT => ordering == Some(Ordering::Greater),
+            Comparison::GE => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+// parses everything on `line` from `start` on
+//
+// or      = and ("||" and)*
+// and     = not ("&&" not)*
+// not     = "!" not | compare
+// compare = primary (("==" | "!=" | "<" | "<=" | ">" | ">=") primary)?
+// primary = number | string | name | "defined" "(" name ")" | "(" or ")"
+pub fn parse(line: &Line, start: usize) -> Result<Condition, Diagnostic> {
+    if start >= line.len() {
+        return Err(line.error("expected a condition"));
+    }
+
+    let (res, end) = parse_tokens(&line.tokens, start)?;
+
+    if end < line.len() {
+        return Err(line.error_at(end, format!("unexpected token {:?} in condition", line[end])));
+    }
+
+    return Ok(res);
+}
+
+// parses the condition starting at `tokens[start]`, up to the first token that can not continue it
+// returns the condition and the index of that token
+pub fn parse_tokens(tokens: &[SpannedToken], start: usize) -> Result<(Condition, usize), Diagnostic> {
+    let mut parser = Parser { tokens, i: start };
+    let res = parser.or()?;
+
+    return Ok((res, parser.i));
+}
+
+struct Parser<'a> {
+    tokens: &'a [SpannedToken],
+    i: usize,
+}
+
+impl Parser<'_> {
+    fn get(&self, index: usize) -> Option<&Token> {
+        self.tokens.get(index).map(|t| &t.token)
+    }
+
+    // an error at the token at `index`, or at the last token if the condition ends before it
+    fn error_at(&self, index: usize, message: impl Into<String>) -> Diagnostic {
+        self.tokens.get(index).or(self.tokens.last()).unwrap().error(message)
+    }
+
+    fn peek_op(&self) -> Option<&str> {
+        match self.get(self.i) {
+            Some(Token::IDENT(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), Diagnostic> {
+        if self.get(self.i) != Some(&token) {
+            return Err(self.error_at(self.i, format!("expected {what}")));
+        }
+
+        self.i += 1;
+        return Ok(());
+    }
+
+    fn or(&mut self) -> Result<Condition, Diagnostic> {
+        let mut left = self.and()?;
+
+        while self.peek_op() == Some("||") {
+            self.i += 1;
+            left = Condition::OR(Box::new(left), Box::new(self.and()?));
+        }
+
+        return Ok(left);
+    }
+
+    fn and(&mut self) -> Result<Condition, Diagnostic> {
+        let mut left = self.not()?;
+
+        while self.peek_op() == Some("&&") {
+            self.i += 1;
+            left = Condition::AND(Box::new(left), Box::new(self.not()?));
+        }
+
+        return Ok(left);
+    }
+
+    fn not(&mut self) -> Result<Condition, Diagnostic> {
+        // `!!` is tokenized as a single word
+        if let Some(op) = self.peek_op() {
+            if op.len() > 0 && op.chars().all(|c| c == '!') {
+                let count = op.len();
+                self.i += 1;
+
+                let mut res = self.not()?;
+                for _ in 0..count {
+                    res = Condition::NOT(Box::new(res));
+                }
+
+                return Ok(res);
+            }
+        }
+
+        return self.compare();
+    }
+
+    fn compare(&mut self) -> Result<Condition, Diagnostic> {
+        let left = self.primary()?;
+
+        let op = match self.peek_op().and_then(Comparison::from_str) {
+            Some(op) => op,
+            None => return Ok(left),
+        };
+        self.i += 1;
+
+        return Ok(Condition::COMPARE(Box::new(left), op, Box::new(self.primary()?)));
+    }
+
+    fn primary(&mut self) -> Result<Condition, Diagnostic> {
+        let token = match self.tokens.get(self.i) {
+            Some(token) => token.clone(),
+            None => return Err(self.error_at(self.i, "unexpected end of condition")),
+        };
+        self.i += 1;
+
+        match &token.token {
+            Token::NUMBER(_) | Token::STRING(_) => return Ok(Condition::VALUE(token)),
+            Token::LPAREN => {
+                let res = self.or()?;
+                self.expect(Token::RPAREN, "`)`")?;
+
+                return Ok(res);
+            }
+            Token::IDENT(s) if s == "defined" => {
+                self.expect(Token::LPAREN, "`(` after `defined`")?;
+
+                let name = match self.tokens.get(self.i) {
+                    Some(name @ SpannedToken { token: Token::IDENT(_), .. }) => name.clone(),
+                    _ => return Err(self.error_at(self.i, "expected a name")),
+                };
+                self.i += 1;
+
+                self.expect(Token::RPAREN, "`)`")?;
+
+                return Ok(Condition::DEFINED(name));
+            }
+            Token::IDENT(s) if s.chars().all(|c| "=!<>&|".contains(c)) => {
+                return Err(token.error(format!("expected a value, found `{s}`")));
+            }
+            Token::IDENT(_) => return Ok(Condition::VALUE(token)),
+            t => return Err(token.error(format!("unexpected token {t:?} in condition"))),
+        }
+    }
+}
+
+impl Condition {
+    // folds everything that is known while assembling, leaving only the parts that depend on the runtime
+    // `&&` and `||` short circuit, so `defined(X) && X > 1` does not need `X` to be defined
+    pub fn simplify(&self, defines: &Defines) -> Result<Condition, Diagnostic> {
+        match self {
+            Condition::VALUE(token) => {
+                return match &token.token {
+                    Token::IDENT(name) if defines.runtime.contains(name) => Ok(self.clone()),
+                    _ => Ok(Condition::VALUE(resolve(token, defines, 0)?)),
+                };
+            }
+            Condition::DEFINED(token) => {
+                let defined = match &token.token {
+                    Token::IDENT(name) => defines.values.contains_key(name) || defines.runtime.contains(name),
+                    _ => false,
+                };
+
+                return Ok(constant(token, defined));
+            }
+            Condition::NOT(cond) => {
+                let cond = cond.simplify(defines)?;
+
+                return match cond.truth() {
+                    Some(b) => Ok(constant(cond.token(), !b)),
+                    None => Ok(Condition::NOT(Box::new(cond))),
+                };
+            }
+            Condition::AND(left, right) | Condition::OR(left, right) => {
+                let is_and = matches!(self, Condition::AND(..));
+                let left = left.simplify(defines)?;
+
+                // `false && x` and `true || x`
+                match left.truth() {
+                    Some(b) if b != is_and => return Ok(constant(left.token(), b)),
+                    Some(_) => return right.simplify(defines).map(|right| right.to_bool()),
+                    None => {}
+                }
+
+                let right = right.simplify(defines)?;
+
+                return match right.truth() {
+                    Some(b) if b != is_and => Ok(constant(right.token(), b)),
+                    Some(_) => Ok(left),
+                    None if is_and => Ok(Condition::AND(Box::new(left), Box::new(right))),
+                    None => Ok(Condition::OR(Box::new(left), Box::new(right))),
+                };
+            }
+            Condition::COMPARE(left, op, right) => {
+                // compared with a runtime name, a name that is not defined is a value the runtime knows too, like `PLATFORM_WIN32`
+                let (left, right) = if left.is_runtime(defines) || right.is_runtime(defines) {
+                    (left.simplify_runtime(defines)?, right.simplify_runtime(defines)?)
+                } else {
+                    (left.simplify(defines)?, right.simplify(defines)?)
+                };
+
+                let ordering = match (left.constant(), right.constant()) {
+                    (Some(Token::NUMBER(a)), Some(Token::NUMBER(b))) => a.compare(b),
+                    (Some(Token::STRING(a)), Some(Token::STRING(b))) => Some(a.cmp(b)),
+                    (Some(_), Some(_)) => return Err(left.token().error("cannot compare a number with a string")),
+                    _ => return Ok(Condition::COMPARE(Box::new(left), *op, Box::new(right))),
+                };
+
+                return Ok(constant(left.token(), op.matches(ordering)));
+            }
+        }
+    }
+
+    fn is_runtime(&self, defines: &Defines) -> bool {
+        return matches!(self, Condition::VALUE(SpannedToken { token: Token::IDENT(name), .. }) if defines.runtime.contains(name));
+    }
+
+    // like `simplify`, but leaves names that are not defined as they are
+    fn simplify_runtime(&self, defines: &Defines) -> Result<Condition, Diagnostic> {
+        match self {
+            Condition::VALUE(SpannedToken { token: Token::IDENT(name), .. }) if !defines.values.contains_key(name) => return Ok(self.clone()),
+            cond => return cond.simplify(defines),
+        }
+    }
+
+    // the value of a fully simplified condition, `None` if it depends on the runtime
+    pub fn truth(&self) -> Option<bool> {
+        match self.constant()? {
+            Token::NUMBER(n) => Some(n.compare(&Number::UNSIGNED(0)) != Some(Ordering::Equal)),
+            Token::STRING(s) => Some(s.len() > 0),
+            _ => None,
+        }
+    }
+
+    // the `left op right` form that `if_block!` takes, for conditions left for the runtime
+    pub fn runtime_parts(&self) -> Result<(SpannedToken, Comparison, SpannedToken), Diagnostic> {
+        match self {
+            Condition::VALUE(token) if matches!(token.token, Token::IDENT(_)) => {
+                let zero = SpannedToken { token: Token::NUMBER(Number::UNSIGNED(0)), ..token.clone() };
+                return Ok((token.clone(), Comparison::NE, zero));
+            }
+            Condition::COMPARE(left, op, right) => {
+                if let (Condition::VALUE(left), Condition::VALUE(right)) = (left.as_ref(), right.as_ref()) {
+                    return Ok((left.clone(), *op, right.clone()));
+                }
+            }
+            Condition::NOT(cond) => {
+                if let Ok((left, op, right)) = cond.runtime_parts() {
+                    return Ok((left, op.invert(), right));
+                }
+            }
+            _ => {}
+        }
+
+        return Err(self.token().error("conditions decided by the runtime can only compare two values")
+            .with_help("parts of the condition that only use defined names are decided while assembling"));
+    }
+
+    // the first token of the condition, for errors
+    pub fn token(&self) -> &SpannedToken {
+        match self {
+            Condition::VALUE(token) | Condition::DEFINED(token) => token,
+            Condition::NOT(cond) => cond.token(),
+            Condition::AND(left, _) | Condition::OR(left, _) | Condition::COMPARE(left, _, _) => left.token(),
+        }
+    }
+
+    fn constant(&self) -> Option<&Token> {
+        match self {
+            Condition::VALUE(SpannedToken { token: t @ (Token::NUMBER(_) | Token::STRING(_)), .. }) => Some(t),
+            _ => None,
+        }
+    }
+
+    // the result of `&&` and `||` is a truth value, not whatever the operand was
+    fn to_bool(self) -> Condition {
+        match self.truth() {
+            Some(b) => constant(self.token(), b),
+            None => self,
+        }
+    }
+}
+
+fn constant(at: &SpannedToken, value: bool) -> Condition {
+    Condition::VALUE(SpannedToken { token: Token::NUMBER(Number::UNSIGNED(value as u64)), ..at.clone() })
+}
+
+// how a value in a runtime condition is passed to `if_block!`
+pub fn runtime_value(token: &SpannedToken) -> String {
+    match &token.token {
+        Token::IDENT(s) => s.clone(),
+        Token::NUMBER(n) => n.to_string(),
+        Token::STRING(s) => format!("{s:?}"),
+        t => format!("{t:?}"),
+    }
+}
+
+// the number or string a name in a condition stands for
+fn resolve(token: &SpannedToken, defines: &Defines, depth: usize) -> Result<SpannedToken, Diagnostic> {
+    match &token.token {
+        Token::NUMBER(_) | Token::STRING(_) => return Ok(token.clone()),
+        Token::IDENT(name) => {
+            let value = match defines.values.get(name) {
+                Some(value) => value,
+                None => return Err(token.error(format!("`{name}` is not defined"))
+                    .with_help(format!("define it with `.define {name} <value>` or `-D {name}=<value>`, or mark it as provided by the runtime with `.runtime {name}`"))),
+            };
+
+            if depth >= MAX_DEPTH {
+                return Err(token.error(format!("`{name}` exceeded the define depth limit of {MAX_DEPTH}")));
+            }
+
+            return match value.tokens.as_slice() {
+                // errors point at the name used in the condition, not at the define
+                [value] => resolve(value, defines, depth + 1)
+                    .map(|value| SpannedToken { token: value.token, ..token.clone() })
+                    .map_err(|e| Diagnostic { file_id: token.file_id, line: token.line, col_start: token.col_start, col_end: token.col_end, ..e }),
+                _ => Err(token.error(format!("`{name}` is not a single number or string"))),
+            };
+        }
+        t => return Err(token.error(format!("unexpected token {t:?} in condition"))),
+    }
+}
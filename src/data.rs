@@ -0,0 +1,104 @@
+use half::f16;
+
+use crate::{number::Number, tokenizer::Type};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataValue {
+    SCALAR(Number),
+    STRING(String),
+    ARRAY(Vec<Number>),
+}
+
+// a named constant from the `.data` section
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub name: String,
+    pub typ: Vec<Type>,
+    pub value: DataValue,
+}
+
+impl Data {
+    // the bytes stored in the data section, every element is little endian
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        match &self.value {
+            DataValue::SCALAR(n) => {
+                if self.typ.len() != 1 {
+                    return Err(format!("expected a scalar type for `{}`", self.name));
+                }
+
+                return encode(n, &self.typ[0]);
+            }
+            DataValue::STRING(s) => {
+                if self.typ != vec![Type::POINTER, Type::U8] {
+                    return Err(format!("strings must have type `char*`, `{}` does not", self.name));
+                }
+
+                return Ok(s.as_bytes().to_vec());
+            }
+            DataValue::ARRAY(values) => {
+                if self.typ.len() != 2 || self.typ[0] != Type::POINTER {
+                    return Err(format!("arrays must have a pointer type, `{}` does not", self.name));
+                }
+
+                let mut res = Vec::new();
+                for value in values {
+                    res.append(&mut encode(value, &self.typ[1])?);
+                }
+
+                return Ok(res);
+            }
+        }
+    }
+}
+
+fn integer(n: &Number) -> Option<i128> {
+    match n {
+        Number::SIGNED(i) => Some(*i as i128),
+        Number::UNSIGNED(u) => Some(*u as i128),
+        Number::DECIMAL(_) => None,
+        Number::TYPED(_, n) => integer(n),
+    }
+}
+
+macro_rules! encode_int {
+    ($n:expr, $typ:ty) => {
+        match integer($n).map(<$typ>::try_from) {
+            Some(Ok(v)) => Ok(v.to_le_bytes().to_vec()),
+            Some(Err(_)) => Err(format!("{} does not fit in a {}", $n, stringify!($typ))),
+            None => Err(format!("expected an integer for a {}, found {}", stringify!($typ), $n)),
+        }
+    };
+}
+
+// a finite value that becomes infinite does not fit, infinity itself does
+macro_rules! encode_float {
+    ($n:expr, $typ:ty, $narrow:expr) => {{
+        let v = f64::from($n.clone());
+        let narrowed: $typ = $narrow(v);
+
+        if v.is_finite() && narrowed.is_infinite() {
+            // with an exponent, the digits of a value this large are not readable
+            Err(format!("{v:e} does not fit in a {}", stringify!($typ)))
+        } else {
+            Ok(narrowed.to_le_bytes().to_vec())
+        }
+    }};
+}
+
+// encodes a single value as the given scalar type, checking that it fits
+pub fn encode(n: &Number, typ: &Type) -> Result<Vec<u8>, String> {
+    match typ {
+        Type::I8 => encode_int!(n, i8),
+        Type::I16 => encode_int!(n, i16),
+        Type::I32 => encode_int!(n, i32),
+        Type::I64 => encode_int!(n, i64),
+        Type::U8 => encode_int!(n, u8),
+        Type::U16 => encode_int!(n, u16),
+        Type::U32 => encode_int!(n, u32),
+        Type::U64 => encode_int!(n, u64),
+        Type::F16 => encode_float!(n, f16, f16::from_f64),
+        Type::F32 => encode_float!(n, f32, |v| v as f32),
+        Type::F64 => Ok(f64::from(n.clone()).to_le_bytes().to_vec()),
+        _ => Err(format!("cannot store a constant of type {typ:?}")),
+    }
+}
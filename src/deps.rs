@@ -0,0 +1,70 @@
+// the `.d` file written next to every output, it lists what the output was assembled from so unchanged files can be skipped
+//
+// # defines 9f2c4e0a1b3d5f70
+// # 1a2b3c4d5e6f7081 lib/io.rasm
+// lib/io.rbb: lib/io.rasm
+//
+// the hashes are in `#` comments, so the file can also be read by make
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepFile {
+    pub defines: u64,              // hash of the defines the output was assembled with
+    pub files: Vec<(String, u64)>, // every file that was read, with the hash of its contents
+}
+
+impl DepFile {
+    pub fn render(&self, output: &str) -> String {
+        let mut res = format!("# defines {:016x}\n", self.defines);
+
+        for (path, hash) in &self.files {
+            res += &format!("# {hash:016x} {path}\n");
+        }
+
+        let files: Vec<String> = self.files.iter().map(|(path, _)| path.clone()).collect();
+
+        return res + &make_rule(output, &files);
+    }
+
+    // `None` if the file was not written by `render`
+    pub fn parse(s: &str) -> Option<DepFile> {
+        let mut lines = s.lines();
+
+        let defines = lines.next()?.strip_prefix("# defines ")?;
+        let mut res = DepFile { defines: u64::from_str_radix(defines, 16).ok()?, files: Vec::new() };
+
+        for line in lines {
+            let line = match line.strip_prefix("# ") {
+                Some(line) => line,
+                None => break,
+            };
+
+            let (hash, path) = line.split_once(' ')?;
+            res.files.push((path.to_string(), u64::from_str_radix(hash, 16).ok()?));
+        }
+
+        return Some(res);
+    }
+}
+
+// FNV-1a, it only has to notice that a file changed and stays the same between versions of rust
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut res: u64 = 0xcbf29ce484222325;
+
+    for b in bytes {
+        res ^= *b as u64;
+        res = res.wrapping_mul(0x100000001b3);
+    }
+
+    return res;
+}
+
+// `target: dep dep...`
+pub fn make_rule(target: &str, deps: &Vec<String>) -> String {
+    let deps: Vec<String> = deps.iter().map(|dep| escape(dep)).collect();
+
+    return format!("{}: {}\n", escape(target), deps.join(" "));
+}
+
+// make splits on spaces, treats `#` as a comment and expands `$`
+fn escape(path: &str) -> String {
+    return path.replace(" ", "\\ ").replace("#", "\\#").replace("$", "$$");
+}
@@ -0,0 +1,140 @@
+use std::{error::Error, fmt};
+
+use colored::Colorize;
+
+use crate::source_map::SourceMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Severity {
+    ERROR,
+    WARNING, // does not stop assembling, unless the assembler is strict
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file_id: usize,   // index into the `SourceMap`
+    pub line: usize,      // 1 based, 0 if the error is about the whole file
+    pub col_start: usize, // 0 based, inclusive
+    pub col_end: usize,   // 0 based, exclusive
+    pub message: String,
+    pub help: Option<String>,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    pub fn new(file_id: usize, line: usize, col_start: usize, col_end: usize, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            file_id,
+            line,
+            col_start,
+            col_end,
+            message: message.into(),
+            help: None,
+            severity: Severity::ERROR,
+        }
+    }
+
+    pub fn warning(mut self) -> Diagnostic {
+        self.severity = Severity::WARNING;
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help = Some(help.into());
+        self
+    }
+
+    // renders the diagnostic as a caret underlined excerpt of the file it points into
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut res = String::new();
+
+        let label = match self.severity {
+            Severity::ERROR => "error".red().bold(),
+            Severity::WARNING => "warning".yellow().bold(),
+        };
+
+        res += &format!("{}{} {}\n", label, ":".bold(), self.message.bold());
+
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        // line 0 is used for errors about a file as a whole
+        if self.line == 0 {
+            res += &format!("{}{} {}\n", gutter, "-->".blue().bold(), sources.path(self.file_id));
+        } else {
+            res += &format!("{}{} {}:{}:{}\n", gutter, "-->".blue().bold(), sources.path(self.file_id), self.line, self.col_start + 1);
+        }
+
+        if let Some(text) = sources.line(self.file_id, self.line) {
+            let text = text.trim_end_matches('\r').replace("\t", " ");
+            let width = self.col_end.saturating_sub(self.col_start).max(1);
+
+            res += &format!("{} {}\n", gutter, "|".blue().bold());
+            res += &format!("{} {} {}\n", number.blue().bold(), "|".blue().bold(), text);
+            res += &format!("{} {} {}{}\n", gutter, "|".blue().bold(), " ".repeat(self.col_start), match self.severity {
+                Severity::ERROR => "^".repeat(width).red().bold(),
+                Severity::WARNING => "^".repeat(width).yellow().bold(),
+            });
+        }
+
+        if let Some(help) = &self.help {
+            res += &format!("{} {} {}: {}\n", gutter, "=".blue().bold(), "help".bold(), help);
+        }
+
+        return res;
+    }
+}
+
+// lets `?` turn a single error into an error list
+impl From<Diagnostic> for Vec<Diagnostic> {
+    fn from(diagnostic: Diagnostic) -> Self {
+        vec![diagnostic]
+    }
+}
+
+// every error from a failed run and the warnings found on the way, along with the sources they point into
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    pub sources: SourceMap,
+}
+
+impl Diagnostics {
+    pub fn new(mut errors: Vec<Diagnostic>, sources: SourceMap) -> Diagnostics {
+        errors.sort_by_key(|e| (e.file_id, e.line, e.col_start));
+
+        Diagnostics { errors, warnings: Vec::new(), sources }
+    }
+
+    pub fn with_warnings(mut self, mut warnings: Vec<Diagnostic>) -> Diagnostics {
+        warnings.sort_by_key(|w| (w.file_id, w.line, w.col_start));
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // warnings and errors in the order they appear in the sources
+    pub fn render(&self) -> String {
+        let mut all: Vec<&Diagnostic> = self.warnings.iter().chain(self.errors.iter()).collect();
+        all.sort_by_key(|d| (d.file_id, d.line, d.col_start));
+
+        all.iter().map(|d| d.render(&self.sources)).collect::<Vec<String>>().join("\n")
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+impl Error for Diagnostics {}
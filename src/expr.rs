@@ -4,7 +4,7 @@ use rainbow_wrapper::generation::{generate_function, Arg, generate_import};
 use rainbow_wrapper::*;
 
 use crate::instruction::Instruction;
-use crate::parser::to_rb_type;
+use crate::lower::to_rb_type;
 use crate::r#struct::Struct;
 
 #[derive(Debug)]
@@ -24,7 +24,7 @@ pub enum Expr {
 }
 
 impl Expr {
-    // TODO: arg length checking
+    // the operands of instructions were checked against `Instruction::signature` while parsing
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Expr::INSTR(instruction, args) => {
@@ -55,7 +55,7 @@ impl Expr {
                     Instruction::AND   => and!  (args[0].clone(), args[1].clone(), args[2].clone()),
                     Instruction::OR    => or!   (args[0].clone(), args[1].clone(), args[2].clone()),
                     Instruction::XOR   => xor!  (args[0].clone(), args[1].clone(), args[2].clone()),
-                    Instruction::NOT   => not!  (args[0].clone(), args[2].clone()),
+                    Instruction::NOT   => not!  (args[0].clone(), args[1].clone()),
                     Instruction::LSH   => lsh!  (args[0].clone(), args[1].clone(), args[2].clone()),
                     Instruction::RSH   => rsh!  (args[0].clone(), args[1].clone(), args[2].clone()),
                     
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, fs, io::{self, Write}, path::Path, process};
+
+// everything the assembler reads or writes goes through this, so it can run without touching disk
+pub trait FileSystem {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        return String::from_utf8(self.read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    // either writes all of `contents` or leaves the file as it was
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()>;
+
+    fn exists(&self, path: &str) -> bool;
+
+    // every file below `dir`, including the ones in subdirectories
+    fn files(&self, dir: &str) -> io::Result<Vec<String>>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    // writes to a temporary file next to `path` and renames it over `path`,
+    // so a crash or a failed write never leaves a missing or truncated file behind
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        let target = Path::new(path);
+        let name = match target.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("`{path}` is not a file"))),
+        };
+
+        let temp = target.with_file_name(format!(".{name}.{}.tmp", process::id()));
+
+        let res = fs::File::create(&temp)
+            .and_then(|mut file| { file.write_all(contents)?; file.sync_all() })
+            .and_then(|_| fs::rename(&temp, target));
+
+        if res.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        return res;
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        Path::new(path).exists()
+    }
+
+    fn files(&self, dir: &str) -> io::Result<Vec<String>> {
+        let mut path_queue: Vec<String> = Vec::new();
+        let mut res = Vec::new();
+
+        path_queue.push(dir.to_string());
+
+        while path_queue.len() > 0 {
+            let path = path_queue.remove(0);
+            let paths = fs::read_dir(path.clone())?;
+
+            for path in paths {
+                let dir_entry = path?;
+
+                // paths are strings everywhere else, so files whose names are not UTF-8 can not be included anyway
+                let path = match dir_entry.path().to_str() {
+                    Some(path) => path.to_string(),
+                    None => continue,
+                };
+
+                if dir_entry.metadata()?.is_dir() {
+                    path_queue.push(path);
+                } else if dir_entry.metadata()?.is_file() {
+                    res.push(path.replace("\\", "/"));
+                }
+            }
+        }
+
+        return Ok(res);
+    }
+}
+
+// a file system that only lives in memory, paths are used as they are given
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFileSystem {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem { files: HashMap::new() }
+    }
+
+    pub fn with_file(mut self, path: &str, contents: impl Into<Vec<u8>>) -> MemoryFileSystem {
+        self.files.insert(normalize(path), contents.into());
+        self
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Vec<u8>> {
+        self.files.get(&normalize(path))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        match self.files.get(&normalize(path)) {
+            Some(contents) => Ok(contents.clone()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("no such file `{path}`"))),
+        }
+    }
+
+    fn write(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        self.files.insert(normalize(path), contents.to_vec());
+
+        return Ok(());
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        let path = normalize(path);
+
+        return self.files.contains_key(&path) || self.files.keys().any(|f| in_folder(f, &path));
+    }
+
+    fn files(&self, dir: &str) -> io::Result<Vec<String>> {
+        let dir = normalize(dir);
+
+        return Ok(self.files.keys().filter(|f| in_folder(f, &dir)).cloned().collect());
+    }
+}
+
+// whether the normalized `file` is somewhere below the normalized `dir`, `.` only has the relative paths in it
+fn in_folder(file: &str, dir: &str) -> bool {
+    if dir == "." {
+        return !file.starts_with('/');
+    }
+
+    return file.starts_with(&folder_prefix(dir));
+}
+
+// `a/` for `a`, and `/` for the root
+fn folder_prefix(dir: &str) -> String {
+    if dir.ends_with('/') {
+        return dir.to_string();
+    }
+
+    return dir.to_string() + "/";
+}
+
+// `./a//b\c`, `a/x/../b/c` and `a/b/c` name the same file, absolute paths keep their leading `/`
+pub(crate) fn normalize(path: &str) -> String {
+    let absolute = path.starts_with('/') || path.starts_with('\\');
+    let mut parts: Vec<&str> = Vec::new();
+
+    for part in path.split(|c| c == '/' || c == '\\') {
+        match part {
+            "" | "." => {}
+            // `..` at the start of a relative path can not be resolved, and there is nothing above the root
+            ".." => match parts.last() {
+                Some(last) if *last != ".." => {
+                    parts.pop();
+                }
+                _ if absolute => {}
+                _ => parts.push(part),
+            },
+            part => parts.push(part),
+        }
+    }
+
+    if absolute {
+        return String::from("/") + &parts.join("/");
+    }
+
+    if parts.len() == 0 {
+        return String::from(".");
+    }
+
+    return parts.join("/");
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     NOP,
     PUSH,
@@ -34,4 +34,104 @@ pub enum Instruction {
     FREE,
     CALLC,
     CMP,
-}
\ No newline at end of file
+}
+
+// what an operand of an instruction can be
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Operand {
+    VALUE,       // [imm/var], a number, string, name or variable
+    DYN_VALUE,   // [imm/var*], a value or a dynamic `@variable`
+    VAR,         // [var], it is written to so it has to be a variable
+    DYN_VAR,     // [var*], a variable or a dynamic `@variable`
+    FUNC,        // [func/var], a function name, an imported `@function` or a variable
+    NAME,        // [name/var]
+    TYPE,        // [type/var]
+    CONDITION,   // `==`, `!=`, `>=`, `>`, `<=` or `<`
+}
+
+// the operands an instruction takes, the ones after `required` can be left out
+#[derive(Debug, Clone, Copy)]
+pub struct Signature {
+    pub operands: &'static [Operand],
+    pub required: usize,
+}
+
+impl Instruction {
+    // matches the instruction table in spec.md
+    pub fn signature(&self) -> Signature {
+        use Operand::*;
+
+        let (operands, required): (&'static [Operand], usize) = match self {
+            Instruction::NOP   => (&[], 0),
+            Instruction::PUSH  => (&[VALUE], 1),
+            Instruction::POP   => (&[VAR], 1),
+            Instruction::PEEK  => (&[VALUE, VAR], 2),
+            Instruction::CALL  => (&[FUNC], 1),
+            Instruction::ADD   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::SUB   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::MUL   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::DIV   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::JMP   => (&[VALUE], 1),
+            Instruction::JNE   => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::JE    => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::JGE   => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::JG    => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::JLE   => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::JL    => (&[VALUE, VALUE, VALUE], 3),
+            Instruction::MOV   => (&[DYN_VALUE, DYN_VAR], 2),
+            Instruction::AND   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::OR    => (&[VALUE, VALUE, VAR], 3),
+            Instruction::XOR   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::NOT   => (&[VALUE, VAR], 2),
+            Instruction::LSH   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::RSH   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::VAR   => (&[TYPE, NAME], 2),
+            Instruction::RET   => (&[VALUE], 0),
+            Instruction::DEREF => (&[VALUE, VAR], 2),
+            Instruction::REF   => (&[VALUE, VAR], 2),
+            Instruction::INST  => (&[NAME, VAR], 2),
+            Instruction::MOD   => (&[VALUE, VALUE, VAR], 3),
+            Instruction::PMOV  => (&[VALUE, VAR, VALUE], 3),
+            Instruction::ALLOC => (&[TYPE, VALUE, VAR], 3),
+            Instruction::FREE  => (&[VALUE, VALUE], 1),
+            Instruction::CALLC => (&[VALUE, TYPE, VALUE], 3),
+            Instruction::CMP   => (&[CONDITION, VALUE, VALUE, VAR], 4),
+        };
+
+        return Signature { operands, required };
+    }
+}
+
+impl Operand {
+    // how the operand is written in spec.md
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operand::VALUE => "[imm/var]",
+            Operand::DYN_VALUE => "[imm/var*]",
+            Operand::VAR => "[var]",
+            Operand::DYN_VAR => "[var*]",
+            Operand::FUNC => "[func/var]",
+            Operand::NAME => "[name/var]",
+            Operand::TYPE => "[type/var]",
+            Operand::CONDITION => "[condition]",
+        }
+    }
+}
+
+impl Signature {
+    // `ADD [imm/var] [imm/var] [var]`, with optional operands between `{}`
+    pub fn describe(&self, name: &str) -> String {
+        let mut res = name.to_string();
+
+        for (i, operand) in self.operands.iter().enumerate() {
+            if i < self.required {
+                res += &format!(" {}", operand.as_str());
+            } else {
+                res += &format!(" {{{}}}", &operand.as_str()[1..operand.as_str().len() - 1]);
+            }
+        }
+
+        return res;
+    }
+}
@@ -0,0 +1,27 @@
+// RASM as a library, `main.rs` is a small command line wrapper around `Assembler`
+mod assembler;
+mod condition;
+mod data;
+mod deps;
+mod macros;
+mod preprocess;
+mod tokenizer;
+mod number;
+mod parser;
+mod lower;
+mod symbols;
+mod typecheck;
+mod expr;
+mod instruction;
+mod r#struct;
+
+pub mod ast;
+pub mod diagnostic;
+pub mod file_system;
+pub mod manifest;
+pub mod source_map;
+
+pub use assembler::Assembler;
+pub use diagnostic::{Diagnostic, Diagnostics, Severity};
+pub use file_system::{FileSystem, MemoryFileSystem, OsFileSystem};
+pub use manifest::Manifest;
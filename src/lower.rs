@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+
+use half::f16;
+use rainbow_wrapper::{ident, immediate, name, r#extern::Extern, generation::Arg, types::{Type, Value}, wrapper::Wrapper};
+
+use crate::{assembler::{Assembler, IncludeError}, ast::{self, Instr, Item, Program, Stmt}, condition::runtime_value, data::{Data, DataValue}, diagnostic::Diagnostic, expr::Expr, instruction::Operand, number::Number, r#struct::Struct, tokenizer};
+
+// turns the tree of a file into the `Expr`s that are emitted
+// labels, constants and included files are resolved here, and instructions are checked against their signature
+pub fn lower(program: &Program, wrapper: &mut Wrapper, asm: &mut Assembler) -> Result<Vec<Expr>, Vec<Diagnostic>> {
+    let mut lowering = Lowering { wrapper, asm, constants: Vec::new(), errors: Vec::new() };
+
+    // constants can be used before the data section
+    lowering.data(&program.items);
+    let res = lowering.items(&program.items);
+
+    if lowering.errors.len() > 0 {
+        return Err(lowering.errors);
+    }
+
+    return Ok(res);
+}
+
+struct Lowering<'a> {
+    wrapper: &'a mut Wrapper,
+    asm: &'a mut Assembler,
+    constants: Vec<Data>,
+    errors: Vec<Diagnostic>,
+}
+
+impl Lowering<'_> {
+    // pushes every constant into the data section
+    fn data(&mut self, items: &Vec<Item>) {
+        for item in items {
+            match item {
+                Item::DATA(constants) => {
+                    for constant in constants {
+                        let name = &constant.name.name;
+
+                        if self.constants.iter().any(|d| d.name == *name) {
+                            self.errors.push(constant.name.span.error(format!("redefined constant `{name}`")));
+                            continue;
+                        }
+
+                        let data = Data { name: name.clone(), typ: constant.typ.clone(), value: constant.value.clone() };
+
+                        match data.to_bytes() {
+                            Ok(bytes) => {
+                                // strings are pushed where they are used, every use becomes the string itself
+                                if !matches!(data.value, DataValue::STRING(_)) {
+                                    self.wrapper.push_data(name, to_rb_type(data.typ.clone()), bytes);
+                                }
+
+                                self.constants.push(data);
+                            }
+                            Err(e) => self.errors.push(constant.value_span.error(e)),
+                        }
+                    }
+                }
+                Item::MODULE(module) => self.data(&module.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn items(&mut self, items: &Vec<Item>) -> Vec<Expr> {
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        let mut position = 0;
+
+        for item in items {
+            match item {
+                Item::STMT(stmt) => {
+                    self.label(&mut labels, stmt, position);
+                    position += size(stmt);
+                }
+                Item::DATA(_) => {}
+                Item::IMPORT(import) if import.quoted && !import.file.ends_with(".rasm") => {}
+                _ => position += 1,
+            }
+        }
+
+        let mut res: Vec<Expr> = Vec::new();
+
+        for item in items {
+            match item {
+                Item::FUNCTION(function) => {
+                    let args = function.args.iter().map(|arg| Arg { name: arg.name.name.clone(), typ: to_rb_type(arg.typ.clone()) }).collect();
+                    let body = self.block(&function.body);
+
+                    res.push(Expr::FUNCDEF(function.name.name.clone(), args, to_rb_type(function.ret_type.clone()), body));
+                }
+                Item::STRUCT(s) => {
+                    let types = s.fields.iter().map(|field| field.typ.clone()).collect();
+                    let names = s.fields.iter().map(|field| field.name.name.clone()).collect();
+
+                    res.push(Expr::STRUCT(Struct { name: s.name.name.clone(), types, names }));
+                }
+                Item::EXTERN(e) => {
+                    self.asm.use_file(&e.file);
+
+                    res.push(Expr::EXTERN(Extern {
+                        ret_type: to_rb_type(e.ret_type.clone()),
+                        name: e.name.name.clone(),
+                        access_name: e.access_name.as_ref().unwrap_or(&e.name).name.clone(),
+                        arg_types: e.arg_types.iter().map(|t| to_rb_type(t.clone())).collect(),
+                        file: e.file.clone(),
+                    }));
+                }
+                Item::MODULE(module) => {
+                    let body = self.items(&module.items);
+                    res.push(Expr::MODULE(module.name.name.clone(), body));
+                }
+                Item::IMPORT(import) => {
+                    if let Some(expr) = self.import(import) {
+                        res.push(expr);
+                    }
+                }
+                Item::DATA(_) => {}
+                Item::STMT(stmt) => self.stmt(stmt, &labels, &mut res),
+            }
+        }
+
+        return res;
+    }
+
+    fn import(&mut self, import: &ast::Import) -> Option<Expr> {
+        let s = &import.file;
+
+        if !import.quoted {
+            self.asm.use_file(&(s.clone() + ".rbb"));
+            return Some(Expr::IMPORT(s.clone() + ".rbb"));
+        }
+
+        if s.ends_with(".rasm") {
+            let import_path = match self.asm.find_include(s, self.asm.current_file().as_deref()) {
+                Ok(path) => path,
+                Err(e) => {
+                    self.errors.push(import.span.error(e));
+                    return None;
+                }
+            };
+
+            if let Some(import_path) = import_path {
+                match self.asm.assemble_include(&import_path) {
+                    Ok(()) => {}
+                    Err(IncludeError::CYCLE(chain)) => {
+                        self.errors.push(import.span.error(format!("`{s}` includes itself"))
+                            .with_help(format!("include chain: {}", chain.join(" -> "))));
+                        return None;
+                    }
+                    Err(IncludeError::FAILED(mut errors)) => {
+                        errors.push(import.span.error(format!("failed to assemble `{s}`")));
+                        self.errors.append(&mut errors);
+                        return None;
+                    }
+                }
+            }
+
+            return Some(Expr::IMPORT(s.split(".").next().unwrap().to_string() + ".rbb"));
+        } else if s.ends_with(".rbb") {
+            self.asm.use_file(s);
+            self.wrapper.push_import(s);
+        } else {
+            self.asm.use_file(&(s.clone() + ".rbb"));
+            self.wrapper.push_import(&(s.clone() + ".rbb"));
+        }
+
+        return None;
+    }
+
+    // the statements of a function, scope or branch, labels only refer to the block they are in
+    fn block(&mut self, stmts: &Vec<Stmt>) -> Vec<Expr> {
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        let mut position = 0;
+
+        for stmt in stmts {
+            self.label(&mut labels, stmt, position);
+            position += size(stmt);
+        }
+
+        let mut res: Vec<Expr> = Vec::new();
+        for stmt in stmts {
+            self.stmt(stmt, &labels, &mut res);
+        }
+
+        return res;
+    }
+
+    fn label(&mut self, labels: &mut HashMap<String, usize>, stmt: &Stmt, position: usize) {
+        if let Stmt::LABEL(label) = stmt {
+            if labels.contains_key(&label.name) {
+                self.errors.push(label.span.error(format!("redefined label `{}`", label.name)));
+            } else {
+                labels.insert(label.name.clone(), position);
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, labels: &HashMap<String, usize>, res: &mut Vec<Expr>) {
+        match stmt {
+            Stmt::INSTR(instr) => {
+                if let Some(expr) = self.instr(instr, labels) {
+                    res.push(expr);
+                }
+            }
+            Stmt::LABEL(_) => {}
+            Stmt::SCOPE(body) => {
+                let body = self.block(body);
+                res.push(Expr::SCOPE(body));
+            }
+            Stmt::IF(chain) => {
+                for (i, branch) in chain.branches.iter().enumerate() {
+                    // conditions known while assembling were already resolved by the preprocessor
+                    let (left, cond, right) = match branch.condition.runtime_parts() {
+                        Ok(parts) => parts,
+                        Err(e) => {
+                            self.errors.push(e);
+                            continue;
+                        }
+                    };
+                    let (left, cond, right) = (runtime_value(&left), cond.as_str().to_string(), runtime_value(&right));
+
+                    let body = self.block(&branch.body);
+
+                    if i == 0 {
+                        res.push(Expr::IF_BLOCK(left, cond, right, body));
+                    } else {
+                        res.push(Expr::ELSEIF_BLOCK(left, cond, right, body));
+                    }
+                }
+
+                if let Some(body) = &chain.otherwise {
+                    let body = self.block(body);
+                    res.push(Expr::ELSE_BLOCK(body));
+                }
+
+                res.push(Expr::END_BLOCK);
+            }
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr, labels: &HashMap<String, usize>) -> Option<Expr> {
+        let operands: Vec<ast::Operand> = instr.operands.iter().map(|operand| self.resolve(operand, labels)).collect();
+
+        if let Err(mut e) = check_operands(instr, &operands) {
+            self.errors.append(&mut e);
+            return None;
+        }
+
+        let mut wrapped: Vec<Value> = Vec::new();
+
+        for operand in operands {
+            wrapped.push(match operand.value {
+                ast::Value::NAME(s) => name!(s),
+                ast::Value::VAR(s) => ident!(s),
+                ast::Value::NUMBER(n) => to_immediate(n),
+                ast::Value::TYPE(t) => Value::TYPE(to_rb_type(t)),
+                ast::Value::STRING(s) => {
+                    self.wrapper.push_string(&s);
+
+                    Value::IDENT(Wrapper::get_string_name(&s))
+                }
+                ast::Value::LABEL(_) => unreachable!("labels were resolved above"),
+            });
+        }
+
+        return Some(Expr::INSTR(instr.instruction.clone(), wrapped));
+    }
+
+    // labels become the position they point at, and constants are referenced by name
+    fn resolve(&mut self, operand: &ast::Operand, labels: &HashMap<String, usize>) -> ast::Operand {
+        let value = match &operand.value {
+            ast::Value::LABEL(label) => {
+                let position = match labels.get(label) {
+                    Some(position) => *position,
+                    None => {
+                        self.errors.push(operand.span.error(format!("unknown label `{label}`")));
+                        0
+                    }
+                };
+
+                ast::Value::NUMBER(Number::UNSIGNED(position as u64))
+            }
+            ast::Value::NAME(name) => match self.constants.iter().find(|d| d.name == *name) {
+                Some(Data { value: DataValue::STRING(s), .. }) => ast::Value::STRING(s.clone()),
+                Some(_) => ast::Value::VAR(name.clone()),
+                None => operand.value.clone(),
+            },
+            value => value.clone(),
+        };
+
+        return ast::Operand { value, span: operand.span };
+    }
+}
+
+// how many `Expr`s a statement becomes, which is what label positions count
+fn size(stmt: &Stmt) -> usize {
+    match stmt {
+        Stmt::INSTR(_) | Stmt::SCOPE(_) => 1,
+        Stmt::LABEL(_) => 0,
+        Stmt::IF(chain) => chain.branches.len() + chain.otherwise.is_some() as usize + 1,
+    }
+}
+
+// checks the operands of an instruction against the signature of the instruction
+fn check_operands(instr: &Instr, operands: &Vec<ast::Operand>) -> Result<(), Vec<Diagnostic>> {
+    let name = &instr.name.name;
+    let signature = instr.instruction.signature();
+    let count = operands.len();
+
+    if count < signature.required || count > signature.operands.len() {
+        let expected = if signature.required == signature.operands.len() {
+            format!("{}", signature.required)
+        } else if signature.required == 0 {
+            format!("at most {}", signature.operands.len())
+        } else if signature.required + 1 == signature.operands.len() {
+            format!("{} or {}", signature.required, signature.operands.len())
+        } else {
+            format!("{} to {}", signature.required, signature.operands.len())
+        };
+        let plural = if signature.operands.len() == 1 { "" } else { "s" };
+
+        let message = format!("{name} expects {expected} operand{plural}, got {count}");
+
+        // point at the first operand that is too many
+        let error = if count > signature.operands.len() { operands[signature.operands.len()].span.error(message) } else { instr.span().error(message) };
+        return Err(vec![error.with_help(signature.describe(name))]);
+    }
+
+    let mut errors: Vec<Diagnostic> = Vec::new();
+
+    for (i, (operand, given)) in signature.operands.iter().zip(operands).enumerate() {
+        let value = &given.value;
+        let dynamic = matches!(value, ast::Value::NAME(s) if s.starts_with('@'));
+
+        let ok = match operand {
+            Operand::VALUE => matches!(value, ast::Value::NUMBER(_) | ast::Value::STRING(_) | ast::Value::VAR(_)) || matches!(value, ast::Value::NAME(_)) && !dynamic,
+            Operand::DYN_VALUE => matches!(value, ast::Value::NUMBER(_) | ast::Value::STRING(_) | ast::Value::VAR(_) | ast::Value::NAME(_)),
+            Operand::VAR => matches!(value, ast::Value::VAR(_)),
+            Operand::DYN_VAR => matches!(value, ast::Value::VAR(_)) || dynamic,
+            Operand::FUNC => matches!(value, ast::Value::NAME(_) | ast::Value::VAR(_)),
+            Operand::NAME => matches!(value, ast::Value::NAME(_) | ast::Value::VAR(_)) && !dynamic,
+            Operand::TYPE => matches!(value, ast::Value::TYPE(_) | ast::Value::VAR(_)),
+            Operand::CONDITION => matches!(value, ast::Value::NAME(s) if ["==", "!=", ">=", ">", "<=", "<"].contains(&s.as_str())),
+        };
+
+        if ok {
+            continue;
+        }
+
+        let got = match value {
+            ast::Value::NUMBER(_) | ast::Value::LABEL(_) => "a number",
+            ast::Value::STRING(_) => "a string",
+            ast::Value::VAR(_) => "a variable",
+            ast::Value::TYPE(_) => "a type",
+            ast::Value::NAME(_) if dynamic => "a dynamic variable",
+            ast::Value::NAME(_) => "a name",
+        };
+
+        let error = match operand {
+            Operand::VAR | Operand::DYN_VAR => given.span.error(format!("operand {} of {name} is written to, so it has to be a variable, got {got}", i + 1)),
+            _ => given.span.error(format!("operand {} of {name} has to be {}, got {got}", i + 1, operand.as_str())),
+        };
+
+        let help = match value {
+            ast::Value::NAME(_) if dynamic => String::from("dynamic `@` variables can only be used with MOV"),
+            ast::Value::NAME(s) if matches!(operand, Operand::VAR | Operand::DYN_VAR) => format!("use `${s}` to refer to the variable `{s}`"),
+            _ => signature.describe(name),
+        };
+
+        errors.push(error.with_help(help));
+    }
+
+    if errors.len() > 0 {
+        return Err(errors);
+    }
+
+    return Ok(());
+}
+
+// suffixed literals keep their type, everything else is left for rainbow to size
+fn to_immediate(n: Number) -> Value {
+    match n {
+        Number::SIGNED(n) => immediate!(SIGNED(n)),
+        Number::UNSIGNED(n) => immediate!(UNSIGNED(n)),
+        Number::DECIMAL(n) => immediate!(DECIMAL(n)),
+        Number::TYPED(typ, n) => {
+            // the tokenizer already checked that the value fits
+            match typ {
+                tokenizer::Type::I8 => immediate!(I8(i64::from(*n) as i8)),
+                tokenizer::Type::I16 => immediate!(I16(i64::from(*n) as i16)),
+                tokenizer::Type::I32 => immediate!(I32(i64::from(*n) as i32)),
+                tokenizer::Type::I64 => immediate!(I64(i64::from(*n))),
+                tokenizer::Type::U8 => immediate!(U8(u64::from(*n) as u8)),
+                tokenizer::Type::U16 => immediate!(U16(u64::from(*n) as u16)),
+                tokenizer::Type::U32 => immediate!(U32(u64::from(*n) as u32)),
+                tokenizer::Type::U64 => immediate!(U64(u64::from(*n))),
+                tokenizer::Type::F16 => immediate!(F16(f16::from_f64(f64::from(*n)))),
+                tokenizer::Type::F32 => immediate!(F32(f64::from(*n) as f32)),
+                _ => immediate!(F64(f64::from(*n))),
+            }
+        }
+    }
+}
+
+pub fn to_rb_type(t: Vec<tokenizer::Type>) -> Vec<Type> {
+    let mut new_type = Vec::new();
+    for typ in t {
+        new_type.push(typ.to_rbtype());
+    }
+
+    return new_type;
+}
+
+pub fn emit(exprs: &Vec<Expr>) -> Vec<u8> {
+    let mut res: Vec<u8> = Vec::new();
+
+    for expr in exprs {
+        res.append(&mut expr.to_bytes());
+    }
+
+    return res;
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{assembler::Assembler, diagnostic::Diagnostic, file_system::normalize, parser::INSTR_MAP, tokenizer::{tokenize_file, Line, SpannedToken, Token}};
+
+const MAX_DEPTH: usize = 64;
+// macros calling each other more than once can grow exponentially without getting deep
+const MAX_LINES: usize = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<String>,
+    pub labels: HashSet<String>, // labels defined in the body, renamed on every expansion
+    pub body: Vec<Line>,
+}
+
+// removes every `.macro` definition and expands every macro call, including macros from included files
+pub fn expand_macros(mut tokens: Vec<Line>, asm: &mut Assembler) -> Result<Vec<Line>, Vec<Diagnostic>> {
+    let mut errors: Vec<Diagnostic> = Vec::new();
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+
+    import_macros(&tokens, asm.current_file(), asm, &mut macros, &mut HashSet::new());
+
+    for m in collect_macros(&mut tokens, &mut errors) {
+        macros.insert(m.name.clone(), m);
+    }
+
+    let mut res: Vec<Line> = Vec::new();
+    let mut expansions = 0;
+    let mut lines = 0;
+
+    for line in tokens {
+        match expand_line(line, &macros, 0, &mut expansions, &mut lines, &mut errors) {
+            Ok(mut expanded) => res.append(&mut expanded),
+            Err(e) => errors.push(e),
+        }
+
+        // every later call would fail the same way
+        if lines > MAX_LINES {
+            break;
+        }
+    }
+
+    if errors.len() > 0 {
+        return Err(errors);
+    }
+
+    return Ok(res);
+}
+
+// macros defined in `.rasm` files that are included, and the files they include
+fn import_macros(tokens: &Vec<Line>, from: Option<String>, asm: &mut Assembler, macros: &mut HashMap<String, Macro>, visited: &mut HashSet<String>) {
+    for line in tokens {
+        if line.len() < 3 || line[0] != Token::DOT || line[1] != Token::IDENT(String::from("include")) {
+            continue;
+        }
+
+        let file = match &line[2] {
+            Token::STRING(s) if s.ends_with(".rasm") => s,
+            _ => continue,
+        };
+
+        // errors in the included file are reported when it is assembled
+        let path = match asm.find_include(file, from.as_deref()) {
+            Ok(Some(path)) => path,
+            _ => continue,
+        };
+
+        if !visited.insert(normalize(&path)) {
+            continue;
+        }
+
+        let file_id = match asm.load(&path) {
+            Ok(file_id) => file_id,
+            Err(_) => continue,
+        };
+
+        let (mut lines, _) = tokenize_file(&asm.sources.get(file_id).unwrap().contents, file_id);
+
+        import_macros(&lines, Some(path), asm, macros, visited);
+
+        for m in collect_macros(&mut lines, &mut Vec::new()) {
+            macros.insert(m.name.clone(), m);
+        }
+    }
+}
+
+// pulls the macro definitions out of `tokens`, leaving their lines empty
+// the body is found by matching braces, whatever follows the closing `}` on its line is kept
+fn collect_macros(tokens: &mut Vec<Line>, errors: &mut Vec<Diagnostic>) -> Vec<Macro> {
+    let mut res: Vec<Macro> = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let line = &tokens[i];
+
+        if line.len() < 2 || line[0] != Token::DOT || line[1] != Token::IDENT(String::from("macro")) {
+            i += 1;
+            continue;
+        }
+
+        let open = match line.tokens.iter().position(|t| t.token == Token::LCURLY) {
+            Some(open) => open,
+            None => {
+                errors.push(line.error("expected `{` after the macro header"));
+                tokens[i].clear();
+                i += 1;
+                continue;
+            }
+        };
+
+        // an unclosed macro takes the rest of the file
+        let end = find_end(tokens, i, open);
+        let (last, close) = end.unwrap_or((tokens.len() - 1, tokens[tokens.len() - 1].len()));
+
+        let mut body: Vec<Line> = Vec::new();
+        for (j, line) in tokens.iter().enumerate().take(last + 1).skip(i) {
+            let from = if j == i { open + 1 } else { 0 };
+            let to = if j == last { close } else { line.len() };
+
+            if from < to {
+                body.push(line.slice(from..to));
+            }
+        }
+
+        match parse_macro(&tokens[i], open, body) {
+            Ok(m) => {
+                if end.is_none() {
+                    errors.push(tokens[i].error(format!("unclosed macro `{}`", m.name)));
+                } else if INSTR_MAP.contains_key(m.name.as_str()) {
+                    errors.push(tokens[i].error_at(2, format!("cannot define a macro with the same name as the `{}` instruction", m.name)));
+                } else if res.iter().any(|o| o.name == m.name) {
+                    errors.push(tokens[i].error_at(2, format!("redefined macro `{}`", m.name)));
+                } else {
+                    res.push(m);
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+
+        let from = if end.is_some() { close + 1 } else { tokens[last].len() };
+        let rest = tokens[last].slice(from..tokens[last].len());
+        for line in &mut tokens[i..last] {
+            line.clear();
+        }
+        tokens[last] = rest;
+
+        i = last + 1;
+    }
+
+    return res;
+}
+
+// the line and index of the `}` closing the `{` at `open` on line `start`
+fn find_end(tokens: &Vec<Line>, start: usize, open: usize) -> Option<(usize, usize)> {
+    let mut depth = 0;
+
+    for (i, line) in tokens.iter().enumerate().skip(start) {
+        let from = if i == start { open } else { 0 };
+
+        for (j, token) in line.tokens.iter().enumerate().skip(from) {
+            match token.token {
+                Token::LCURLY => depth += 1,
+                Token::RCURLY => depth -= 1,
+                _ => continue,
+            }
+
+            if depth == 0 {
+                return Some((i, j));
+            }
+        }
+    }
+
+    return None;
+}
+
+// `.macro NAME params... {` and the body between the braces
+fn parse_macro(header: &Line, open: usize, body: Vec<Line>) -> Result<Macro, Diagnostic> {
+    let name = match header.get(2) {
+        Some(Token::IDENT(s)) if open > 2 => s.clone(),
+        _ => return Err(header.error_at(2, "expected a macro name")),
+    };
+
+    let mut params: Vec<String> = Vec::new();
+    for i in 3..open {
+        match &header[i] {
+            Token::IDENT(s) => {
+                if params.contains(s) {
+                    return Err(header.error_at(i, format!("duplicate parameter `{s}`")));
+                }
+
+                params.push(s.clone());
+            }
+            t => return Err(header.error_at(i, format!("unexpected token {t:?}")).with_help("expected a parameter name")),
+        }
+    }
+
+    let labels = labels(&body);
+
+    return Ok(Macro { name, params, labels, body });
+}
+
+// the labels defined in a body, like the parser a `:label` is an operand while an instruction on its line has room for it
+fn labels(body: &Vec<Line>) -> HashSet<String> {
+    let mut res = HashSet::new();
+
+    for line in body {
+        let mut room = 0;
+
+        for i in 0..line.len() {
+            match &line[i] {
+                Token::IDENT(s) if INSTR_MAP.contains_key(s.as_str()) => room = INSTR_MAP[s.as_str()].signature().operands.len(),
+                Token::LCURLY | Token::RCURLY => room = 0,
+                // the name of a label, or a part of a module path
+                Token::IDENT(_) if i > 0 && (line[i - 1] == Token::COLON || line[i - 1] == Token::DOT) => {}
+                Token::COLON => match line.get(i + 1) {
+                    Some(Token::IDENT(s)) if room == 0 => {
+                        res.insert(s.clone());
+                    }
+                    _ => room = room.saturating_sub(1),
+                },
+                Token::DOT => {}
+                _ => room = room.saturating_sub(1),
+            }
+        }
+    }
+
+    return res;
+}
+
+// expands every macro call on the line, wherever it is, the code around a call stays on lines of its own
+// `lines` counts the lines every expansion so far produced
+fn expand_line(line: Line, macros: &HashMap<String, Macro>, depth: usize, expansions: &mut usize, lines: &mut usize, errors: &mut Vec<Diagnostic>) -> Result<Vec<Line>, Diagnostic> {
+    // directives work on whole lines
+    if line.get(0) == Some(&Token::DOT) {
+        return Ok(vec![line]);
+    }
+
+    let (start, m) = match (0..line.len()).find(|i| is_call(&line, *i, macros)) {
+        Some(start) => match &line[start] {
+            Token::IDENT(s) => (start, &macros[s]),
+            _ => return Ok(vec![line]),
+        },
+        None => return Ok(vec![line]),
+    };
+
+    if depth >= MAX_DEPTH {
+        return Err(line.error_at(start, format!("macro `{}` exceeded the recursion limit of {MAX_DEPTH}", m.name)));
+    }
+
+    let mut res: Vec<Line> = Vec::new();
+    if start > 0 {
+        res.push(line.slice(0..start));
+    }
+
+    let (args, end) = call_args(&line, start, m, macros);
+    if args.len() != m.params.len() {
+        errors.push(line.slice(start..end).error(format!("macro `{}` expects {} argument(s), got {}", m.name, m.params.len(), args.len())));
+    } else {
+        *expansions += 1;
+        let expansion = *expansions;
+
+        for body_line in &m.body {
+            *lines += 1;
+            if *lines > MAX_LINES {
+                return Err(line.error_at(start, format!("macro `{}` expanded to more than {MAX_LINES} lines", m.name))
+                    .with_help("a macro that uses another macro more than once grows quickly"));
+            }
+
+            let body_line = substitute(m, body_line, &args, expansion, &line.tokens[start]);
+
+            res.append(&mut expand_line(body_line, macros, depth + 1, expansions, lines, errors)?);
+        }
+    }
+
+    // the rest of the line can call macros too
+    if end < line.len() {
+        res.append(&mut expand_line(line.slice(end..line.len()), macros, depth, expansions, lines, errors)?);
+    }
+
+    return Ok(res);
+}
+
+// whether the token at `i` calls a macro, names next to a `.` or after a `:` are part of a module path or a label
+fn is_call(line: &Line, i: usize, macros: &HashMap<String, Macro>) -> bool {
+    let attached = (i > 0 && (line[i - 1] == Token::DOT || line[i - 1] == Token::COLON)) || line.get(i + 1) == Some(&Token::DOT);
+
+    return !attached && matches!(&line[i], Token::IDENT(s) if macros.contains_key(s));
+}
+
+// the arguments of the call at `start` and the index after them
+// once the macro has its arguments, an instruction, another call or a brace starts what follows the call
+fn call_args(line: &Line, start: usize, m: &Macro, macros: &HashMap<String, Macro>) -> (Vec<Vec<SpannedToken>>, usize) {
+    let mut res: Vec<Vec<SpannedToken>> = Vec::new();
+
+    let mut i = start + 1;
+    while i < line.len() {
+        let ends = match &line[i] {
+            Token::LCURLY | Token::RCURLY => true,
+            Token::IDENT(s) => res.len() >= m.params.len() && (INSTR_MAP.contains_key(s.as_str()) || is_call(line, i, macros)),
+            _ => false,
+        };
+        if ends {
+            break;
+        }
+
+        let len = arg_len(line, i);
+        res.push(line.tokens[i..i + len].to_vec());
+        i += len;
+    }
+
+    return (res, i);
+}
+
+// the number of tokens in the argument at `i`, keeping `a.b`, `$a.b` and `:label` together
+fn arg_len(line: &Line, i: usize) -> usize {
+    let mut len = 1;
+
+    match &line[i] {
+        Token::COLON => {
+            if i + 1 < line.len() {
+                len = 2;
+            }
+        }
+        Token::IDENT(_) | Token::VAR(_) => {
+            while i + len + 1 < line.len() && line[i + len] == Token::DOT && matches!(line[i + len + 1], Token::IDENT(_)) {
+                len += 2;
+            }
+        }
+        _ => {}
+    }
+
+    return len;
+}
+
+// the tokens of the body point at the call, so errors in an expansion show where the macro was used
+fn substitute(m: &Macro, line: &Line, args: &Vec<Vec<SpannedToken>>, expansion: usize, call: &SpannedToken) -> Line {
+    let mut tokens: Vec<SpannedToken> = Vec::new();
+
+    for (i, token) in line.tokens.iter().enumerate() {
+        let after_colon = i > 0 && line[i - 1] == Token::COLON;
+        let token = &SpannedToken { token: token.token.clone(), ..call.clone() };
+
+        match &token.token {
+            Token::IDENT(s) if after_colon && m.labels.contains(s) => {
+                let mut token = token.clone();
+                token.token = Token::IDENT(format!("{s}#{expansion}"));
+                tokens.push(token);
+            }
+            Token::IDENT(s) if m.params.contains(s) => {
+                let index = m.params.iter().position(|p| p == s).unwrap();
+                tokens.extend(args[index].iter().cloned());
+            }
+            Token::VAR(s) if m.params.contains(s) => {
+                // `$param` names a variable with the argument's name
+                let index = m.params.iter().position(|p| p == s).unwrap();
+                match args[index].as_slice() {
+                    [SpannedToken { token: Token::IDENT(name) | Token::VAR(name), .. }] => {
+                        let mut token = args[index][0].clone();
+                        token.token = Token::VAR(name.clone());
+                        tokens.push(token);
+                    }
+                    arg => tokens.extend(arg.iter().cloned()),
+                }
+            }
+            _ => tokens.push(token.clone()),
+        }
+    }
+
+    return Line::new(call.line, call.file_id, call.col_start, call.col_end, tokens);
+}
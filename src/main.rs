@@ -1,84 +1,541 @@
-use std::{collections::HashSet, env::{self}, fs, io::Write, path::Path};
+use std::{env::{self}, fs, io::{self, Read, Write}, process::exit, thread, time::{Duration, SystemTime}};
 
-use parser::{emit, parse};
-use rainbow_wrapper::wrapper::Wrapper;
-use tokenizer::{tokenize, Token};
+use colored::Colorize;
+use rasm::{Assembler, Diagnostics, FileSystem, Manifest, OsFileSystem};
 
-mod tokenizer;
-mod number;
-mod parser;
-mod expr;
-mod instruction;
-mod r#struct;
+const USAGE: &str = "usage: rasm [options] <file.rasm>...
+       rasm build [options] [rasm.toml]
+
+Assembles RASM files into rainbow bytecode, writing `<file>.rbb` next to each input.
+`rasm build` assembles the entry points of the project described by a manifest, `rasm.toml` if none is given.
+A `.d` file is written next to every output, files that did not change since are not assembled again.
+Use `-` as a file to read from stdin, the result is written to stdout unless `-o` is given.
+
+options:
+    -o, --output <file>   write the bytecode to <file> (`-` for stdout), only with a single input and not with `build`
+    -l, --link <path>     add a folder to search for included files in, searched in the order given
+    -D <name>[=<value>]   define a name for `.if` conditions and instruction arguments, the value defaults to 1
+    -R, --runtime <name>  leave `.if` chains using <name> for the runtime to decide
+    -f, --force           assemble every file, even the ones that are up to date
+    --strict              make the warnings of the type checker errors
+    -M, --dep-file <file> write the files every output was made from to <file> as make rules
+    -w, --watch           keep running, and assemble again whenever a file the inputs are made from changes
+    -q, --quiet           only print errors, not warnings
+    --print-search-path   print the folders searched for included files and exit
+    -v, --verbose         print more about what is being done
+    -h, --help            print this message
+    -V, --version         print the version
+
+included files are searched for in the folder of the including file, then the `-l` folders,
+then the folders in the RASM_PATH environment variable.
+
+exit codes:
+    0   everything assembled
+    1   at least one file failed to assemble or could not be written
+    2   the arguments or the manifest were invalid";
+
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+enum Verbosity {
+    QUIET,
+    NORMAL,
+    VERBOSE,
+}
+
+struct Options {
+    inputs: Vec<String>,
+    manifest: Option<String>,
+    output: Option<String>,
+    output_dir: Option<String>,
+    bundle: Vec<String>,
+    dep_file: Option<String>,
+    link_paths: Vec<String>,
+    defines: Vec<(String, String)>,
+    runtime: Vec<String>,
+    verbosity: Verbosity,
+    force: bool,
+    strict: bool,
+    watch: bool,
+    print_search_path: bool,
+}
+
+// how often `--watch` looks for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            error(&e);
+            eprintln!("\n{USAGE}");
+            exit(2);
+        }
+    };
+
+    if !apply_manifest(&mut options) {
+        exit(2);
+    }
+
+    let search_path = search_path(&options);
+
+    if options.print_search_path {
+        println!("<folder of the including file>");
+        for (path, from) in &search_path {
+            println!("{path}  ({from})");
+        }
+        exit(0);
+    }
+
+    let mut assembler = new_assembler(&options);
+    let (ok, files) = assemble_all(&mut assembler, &options);
+
+    if options.watch {
+        watch(&args, options, assembler, files);
+    }
 
-    if args.len() < 2 {
-        panic!("expected file");
+    if !ok {
+        exit(1);
     }
-    if !args[1].ends_with(".rasm") {
-        panic!("expected RASM file");
+}
+
+fn new_assembler(options: &Options) -> Assembler {
+    let search_path = search_path(options);
+
+    let mut assembler = Assembler::new().force(options.force).strict(options.strict);
+    for (path, _) in &search_path {
+        assembler = assembler.link_path(path.clone());
+    }
+    for (name, value) in &options.defines {
+        assembler = assembler.define(name.clone(), value.clone());
+    }
+    for name in &options.runtime {
+        assembler = assembler.runtime(name.clone());
+    }
+
+    if options.verbosity == Verbosity::VERBOSE && search_path.len() > 0 {
+        eprintln!("search path: {}", search_path.iter().map(|(path, _)| path.clone()).collect::<Vec<String>>().join(", "));
+    }
+
+    return assembler;
+}
+
+// assembles every input, returning whether everything worked and the files the inputs are made from
+fn assemble_all(assembler: &mut Assembler, options: &Options) -> (bool, Vec<String>) {
+    let mut files: Vec<String> = options.manifest.iter().chain(&options.bundle).cloned().collect();
+
+    if let Some(dir) = &options.output_dir {
+        if let Err(e) = fs::create_dir_all(dir) {
+            error(&format!("failed to create `{dir}`: {e}"));
+            return (false, files);
+        }
+    }
+
+    let mut failed = 0;
+    let mut rules = String::new();
+    for input in &options.inputs {
+        match assemble(assembler, input, options) {
+            Some(output) => rules += &assembler.make_rule(&output),
+            None => failed += 1,
+        }
+
+        // the `.rbb` files are written by rasm, or are only referred to by name
+        for dep in assembler.dependencies() {
+            if !dep.ends_with(".rbb") && !files.contains(dep) {
+                files.push(dep.clone());
+            }
+        }
     }
 
+    if failed > 0 {
+        if options.inputs.len() > 1 {
+            error(&format!("{failed} of {} files failed to assemble", options.inputs.len()));
+        }
+
+        return (false, files);
+    }
+
+    if !bundle(options) {
+        return (false, files);
+    }
+
+    if let Some(dep_file) = &options.dep_file {
+        if let Err(e) = write_output(dep_file, &rules.into_bytes()) {
+            error(&format!("failed to write `{dep_file}`: {e}"));
+            return (false, files);
+        }
+    }
+
+    return (true, files);
+}
+
+// checks `files` for changes every `POLL_INTERVAL` and assembles again when one changed, until rasm is stopped
+// when the manifest changed it is read again, and the last options that worked are kept if it can not be used
+fn watch(args: &Vec<String>, mut options: Options, mut assembler: Assembler, mut files: Vec<String>) -> ! {
+    let mut times = modified(&files);
+
+    loop {
+        if options.verbosity >= Verbosity::NORMAL {
+            eprintln!("watching {} files for changes", files.len());
+        }
+
+        let changed = loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let now = modified(&files);
+            if let Some(i) = (0..files.len()).find(|i| now[*i] != times[*i]) {
+                break files[i].clone();
+            }
+        };
+
+        if options.verbosity >= Verbosity::NORMAL {
+            eprintln!("\n{changed} changed");
+        }
+
+        if options.manifest.as_ref() == Some(&changed) {
+            // `args` were fine the first time, only the manifest can be wrong now
+            let mut reloaded = parse_args(args).unwrap();
+            if apply_manifest(&mut reloaded) {
+                options = reloaded;
+                assembler = new_assembler(&options);
+            }
+        }
+
+        (_, files) = assemble_all(&mut assembler, &options);
+        times = modified(&files);
+    }
+}
+
+// when each file was last changed and its size, `None` for files that can not be read
+// the size catches changes made so quickly after each other that they get the same time
+fn modified(files: &Vec<String>) -> Vec<Option<(SystemTime, u64)>> {
+    return files.iter().map(|file| fs::metadata(file).and_then(|m| Ok((m.modified()?, m.len()))).ok()).collect();
+}
+
+fn parse_args(args: &Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        inputs: Vec::new(),
+        manifest: None,
+        output: None,
+        output_dir: None,
+        bundle: Vec::new(),
+        dep_file: None,
+        link_paths: Vec::new(),
+        defines: Vec::new(),
+        runtime: Vec::new(),
+        verbosity: Verbosity::NORMAL,
+        force: false,
+        strict: false,
+        watch: false,
+        print_search_path: false,
+    };
+
     let mut i = 0;
-    let mut link_paths: HashSet<String> = HashSet::new();
+    if args.get(0).is_some_and(|arg| arg == "build") {
+        options.manifest = Some(String::from("rasm.toml"));
+        i += 1;
+    }
+
     while i < args.len() {
-        match args[i].as_str() {
-            "-l" | "--link" => {
+        let arg = args[i].as_str();
+
+        match arg {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            "-V" | "--version" => {
+                println!("rasm {}", env!("CARGO_PKG_VERSION"));
+                exit(0);
+            }
+            "-o" | "--output" | "-M" | "--dep-file" | "-l" | "--link" | "-D" | "-R" | "--runtime" => {
                 i += 1;
-                add_link_path(args[i].clone(), &mut link_paths);
+                let value = match args.get(i) {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("`{arg}` expects a value")),
+                };
+
+                match arg {
+                    "-o" | "--output" => {
+                        if options.output.is_some() {
+                            return Err(String::from("`-o` can only be given once"));
+                        }
+
+                        options.output = Some(value);
+                    }
+                    "-M" | "--dep-file" => {
+                        if options.dep_file.is_some() {
+                            return Err(String::from("`-M` can only be given once"));
+                        }
+
+                        options.dep_file = Some(value);
+                    }
+                    "-l" | "--link" => options.link_paths.push(value),
+                    "-D" => options.defines.push(parse_define(&value)?),
+                    _ => options.runtime.push(value),
+                }
+            }
+            _ if arg.starts_with("-D") => options.defines.push(parse_define(&arg[2..])?),
+            "-q" | "--quiet" => options.verbosity = Verbosity::QUIET,
+            "-v" | "--verbose" => options.verbosity = Verbosity::VERBOSE,
+            "-f" | "--force" => options.force = true,
+            "--strict" => options.strict = true,
+            "-w" | "--watch" => options.watch = true,
+            "--print-search-path" => options.print_search_path = true,
+            "-" => options.inputs.push(String::from("-")),
+            _ if arg.starts_with("-") => return Err(format!("unknown option `{arg}`")),
+            _ if options.manifest.is_some() => {
+                if !arg.ends_with(".toml") {
+                    return Err(format!("expected a manifest like `rasm.toml`, got `{arg}`"));
+                }
+
+                options.manifest = Some(args[i].clone());
+            }
+            _ => {
+                if !arg.ends_with(".rasm") {
+                    return Err(format!("expected a `.rasm` file, got `{arg}`"));
+                }
+
+                options.inputs.push(args[i].clone());
             }
-            _ => {}
         }
+
         i += 1;
     }
 
-    assemble(args[1].clone(), &mut link_paths);
+    if options.watch && options.inputs.contains(&String::from("-")) {
+        return Err(String::from("`--watch` can not be used when reading from stdin"));
+    }
+
+    if options.manifest.is_some() {
+        if options.inputs.len() > 0 {
+            return Err(String::from("`rasm build` assembles the entry points of the manifest, it does not take files"));
+        }
+
+        if options.output.is_some() {
+            return Err(String::from("`-o` can not be used with `rasm build`, set `output` in the manifest instead"));
+        }
+
+        return Ok(options);
+    }
+
+    if options.inputs.len() == 0 && !options.print_search_path {
+        return Err(String::from("no input files"));
+    }
+
+    if options.output.is_some() && options.inputs.len() > 1 {
+        return Err(String::from("`-o` can only be used with a single input file"));
+    }
+
+    if options.dep_file.is_some() && (options.output.as_deref() == Some("-") || (options.output.is_none() && options.inputs.contains(&String::from("-")))) {
+        return Err(String::from("`-M` can not be used when writing to stdout"));
+    }
+
+    return Ok(options);
 }
 
-pub fn assemble(rasm_file: String, link_paths: &mut HashSet<String>) {
-    println!("assembling {}", rasm_file.replace("\\", "/")); // consistency
+// `NAME=VALUE`, or just `NAME` to define it as 1
+fn parse_define(s: &str) -> Result<(String, String), String> {
+    let (name, value) = match s.split_once('=') {
+        Some((name, value)) => (name, value),
+        None => (s, "1"),
+    };
 
-    let file = rasm_file.split(".rasm").collect::<Vec<&str>>()[0];
-    let folder = rasm_file.split(|c| c == '\\' || c == '/').collect::<Vec<&str>>();
-    let folder = folder[0..folder.len()-1].to_vec().join("/") + "/";
+    if name.len() == 0 || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("invalid define `{s}`, expected `NAME=VALUE`"));
+    }
 
-    add_link_path(folder, link_paths);
+    return Ok((name.to_string(), value.to_string()));
+}
 
-    let contents = fs::read_to_string(file.to_string() + ".rasm").expect("failed to read file");
+// adds what the manifest declares to the options, the manifest comes before the command line
+// returns false after reporting why the manifest could not be read
+fn apply_manifest(options: &mut Options) -> bool {
+    let path = match &options.manifest {
+        Some(path) => path.clone(),
+        None => return true,
+    };
 
-    let mut tokens: Vec<Vec<Token>> = Vec::new();
-    
-    let lines = contents.split("\n");
-    for line in lines {
-        let line = tokenize(line.to_string());
-        tokens.push(line);
+    let manifest = match Manifest::load(&path, &OsFileSystem) {
+        Ok(manifest) => manifest,
+        Err(diagnostics) => {
+            eprintln!("{diagnostics}");
+            error(&format!("could not read the manifest `{path}`"));
+            return false;
+        }
+    };
+
+    options.inputs = manifest.entries;
+    options.output_dir = manifest.output;
+    options.bundle = manifest.bundle;
+    options.strict |= manifest.strict;
+
+    options.link_paths.splice(0..0, manifest.link_paths);
+    options.defines.splice(0..0, manifest.defines);
+    options.runtime.splice(0..0, manifest.runtime);
+
+    return true;
+}
+
+// copies the `.extern` libraries of the manifest into the output folder, returning whether that worked
+fn bundle(options: &Options) -> bool {
+    let dir = match &options.output_dir {
+        Some(dir) => dir,
+        None => return true,
+    };
+
+    let mut res = true;
+    for library in &options.bundle {
+        let name = library.rsplit(|c| c == '/' || c == '\\').next().unwrap();
+        let output = format!("{dir}/{name}");
+
+        if let Err(e) = OsFileSystem.read(library).and_then(|bytes| OsFileSystem.write(&output, &bytes)) {
+            error(&format!("failed to bundle `{library}`: {e}"));
+            res = false;
+        } else if options.verbosity == Verbosity::VERBOSE {
+            eprintln!("bundled {library} into {dir}");
+        }
     }
 
-    // println!("{:#?}", tokens);
+    return res;
+}
 
-    let mut wrapper = Wrapper::new();
+// the `-l` folders in the order they were given, then the ones in RASM_PATH, with where each came from
+fn search_path(options: &Options) -> Vec<(String, &'static str)> {
+    let mut res: Vec<(String, &'static str)> = options.link_paths.iter().map(|path| (path.clone(), "-l")).collect();
 
-    let program = emit(&parse(tokens, &mut wrapper, link_paths));
-    wrapper.push(program);
+    if let Some(paths) = env::var_os("RASM_PATH") {
+        for path in env::split_paths(&paths) {
+            let path = path.to_string_lossy().to_string();
+            if path.len() > 0 {
+                res.push((path, "RASM_PATH"));
+            }
+        }
+    }
+
+    return res;
+}
 
-    let bytes = wrapper.emit();
+// assembles a single input and writes its output, returning the output if it succeeded
+fn assemble(assembler: &mut Assembler, input: &String, options: &Options) -> Option<String> {
+    let name = if input == "-" { String::from("<stdin>") } else { input.replace("\\", "/") }; // consistency
 
-    let rbb_file = file.to_string() + ".rbb";
-    if Path::new(&rbb_file).exists() {
-       fs::remove_file(rbb_file.clone()).unwrap();
+    let output = match (&options.output, &options.output_dir) {
+        (Some(output), _) => output.clone(),
+        (None, _) if input == "-" => String::from("-"),
+        (None, Some(dir)) => format!("{dir}/{}", output_name(input)),
+        (None, None) => input.split(".rasm").collect::<Vec<&str>>()[0].to_string() + ".rbb",
+    };
+
+    if input != "-" && output != "-" {
+        return if build(assembler, input, &name, &output, options) { Some(output) } else { None };
+    }
+
+    if options.verbosity >= Verbosity::NORMAL {
+        eprintln!("assembling {name}");
     }
 
-    let mut file = fs::OpenOptions::new().create_new(true).write(true).open(rbb_file).expect("failed to open file to save");
-    let _ = file.write_all(&bytes);
+    let res = if input == "-" {
+        let mut src = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut src) {
+            error(&format!("failed to read stdin: {e}"));
+            return None;
+        }
+
+        assembler.assemble_str(&src)
+    } else {
+        assembler.assemble_file(input)
+    };
+
+    let bytes = match res {
+        Ok(bytes) => bytes,
+        Err(diagnostics) => {
+            report(&diagnostics, &name, options);
+            return None;
+        }
+    };
+
+    warn(assembler.warnings(), options);
+
+    if let Err(e) = write_output(&output, &bytes) {
+        error(&format!("failed to write `{output}`: {e}"));
+        return None;
+    }
+
+    if options.verbosity == Verbosity::VERBOSE {
+        let output = if output == "-" { "<stdout>" } else { &output };
+        eprintln!("wrote {} bytes to {output}", bytes.len());
+    }
+
+    return Some(output);
+}
+
+// `src/main.rasm` is assembled to `main.rbb` in the output folder
+fn output_name(input: &String) -> String {
+    let name = input.rsplit(|c| c == '/' || c == '\\').next().unwrap();
+
+    return name.split(".rasm").next().unwrap().to_string() + ".rbb";
+}
+
+// assembles a file into a file, skipping it when it is up to date
+fn build(assembler: &mut Assembler, input: &String, name: &String, output: &String, options: &Options) -> bool {
+    if assembler.is_up_to_date(input, output) {
+        if options.verbosity >= Verbosity::NORMAL {
+            eprintln!("{name} is up to date");
+        }
+
+        return true;
+    }
+
+    if options.verbosity >= Verbosity::NORMAL {
+        eprintln!("assembling {name}");
+    }
+
+    match assembler.build(input, output) {
+        Ok(written) => {
+            warn(assembler.warnings(), options);
+
+            if options.verbosity == Verbosity::VERBOSE {
+                eprintln!("wrote {written} bytes to {output}");
+            }
+
+            return true;
+        }
+        Err(diagnostics) => {
+            report(&diagnostics, name, options);
+            return false;
+        }
+    }
 }
 
-// this function shouldnt need to exist
-fn add_link_path(mut folder: String, link_paths: &mut HashSet<String>) {
-    folder = folder.replace("\\", "/");
-    if folder.ends_with("/") {
-        folder = folder[..folder.len()-1].to_string();
+fn write_output(output: &String, bytes: &Vec<u8>) -> io::Result<()> {
+    if output == "-" {
+        let mut stdout = io::stdout();
+        stdout.write_all(bytes)?;
+        return stdout.flush();
     }
-    link_paths.insert(folder);
+
+    return OsFileSystem.write(output, bytes);
+}
+
+fn report(diagnostics: &Diagnostics, name: &String, options: &Options) {
+    warn(diagnostics, options);
+
+    for error in &diagnostics.errors {
+        eprintln!("{}", error.render(&diagnostics.sources));
+    }
+
+    error(&format!("could not assemble `{name}` due to {} previous error(s)", diagnostics.len()));
+}
+
+fn warn(diagnostics: &Diagnostics, options: &Options) {
+    if options.verbosity >= Verbosity::NORMAL {
+        for warning in &diagnostics.warnings {
+            eprintln!("{}", warning.render(&diagnostics.sources));
+        }
+    }
+}
+
+fn error(message: &str) {
+    eprintln!("{}{} {}", "error".red().bold(), ":".bold(), message);
 }
@@ -0,0 +1,376 @@
+use crate::{diagnostic::{Diagnostic, Diagnostics}, file_system::FileSystem, source_map::SourceMap};
+
+// the build configuration of a project, read from `rasm.toml`
+//
+// [build]
+// entry = ["src/main.rasm"]
+// link = ["lib"]
+// output = "build"
+// bundle = ["lib/libc.so"]
+// runtime = ["PLATFORM"]
+// strict = true
+//
+// [defines]
+// DEBUG = 1
+// NAME = "linux"
+//
+// paths are relative to the folder the manifest is in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub entries: Vec<String>,
+    pub link_paths: Vec<String>,
+    pub output: Option<String>,         // folder the entries are assembled into, next to each entry if not given
+    pub bundle: Vec<String>,            // `.extern` libraries copied into the output folder
+    pub defines: Vec<(String, String)>, // values are written the same as in RASM
+    pub runtime: Vec<String>,
+    pub strict: bool,                   // type checker warnings are errors
+}
+
+// the part of TOML the manifest uses: `[section]`, `key = value` and `#` comments,
+// with strings, numbers, booleans and arrays of them as values
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    STRING(String),
+    NUMBER(String), // as it was written
+    BOOL(bool),
+    ARRAY(Vec<Value>),
+}
+
+impl Value {
+    fn describe(&self) -> &'static str {
+        match self {
+            Value::STRING(_) => "a string",
+            Value::NUMBER(_) => "a number",
+            Value::BOOL(_) => "a boolean",
+            Value::ARRAY(_) => "an array",
+        }
+    }
+}
+
+// a `key = value` line
+struct Entry {
+    section: String,
+    key: String,
+    value: Value,
+    line: usize,
+    col_start: usize,
+    col_end: usize,
+}
+
+impl Manifest {
+    // reads the manifest at `path` and makes its paths relative to the working directory
+    pub fn load(path: &str, fs: &dyn FileSystem) -> Result<Manifest, Diagnostics> {
+        let mut sources = SourceMap::new();
+
+        let src = match fs.read_to_string(path) {
+            Ok(src) => src,
+            Err(e) => {
+                let file_id = sources.add(path, String::new());
+                return Err(Diagnostics::new(vec![Diagnostic::new(file_id, 0, 0, 0, format!("failed to read `{path}`: {e}"))], sources));
+            }
+        };
+
+        let file_id = sources.add(path, src.clone());
+        let mut manifest = match Manifest::parse(&src, file_id) {
+            Ok(manifest) => manifest,
+            Err(errors) => return Err(Diagnostics::new(errors, sources)),
+        };
+
+        let folder = match path.replace("\\", "/").rsplit_once('/') {
+            Some((folder, _)) => folder.to_string(),
+            None => String::new(),
+        };
+
+        if folder.len() > 0 {
+            let join = |p: &String| if p.starts_with("/") { p.clone() } else { format!("{folder}/{p}") };
+
+            manifest.entries = manifest.entries.iter().map(join).collect();
+            manifest.link_paths = manifest.link_paths.iter().map(join).collect();
+            manifest.bundle = manifest.bundle.iter().map(join).collect();
+            manifest.output = manifest.output.as_ref().map(join);
+        }
+
+        return Ok(manifest);
+    }
+
+    pub fn parse(src: &str, file_id: usize) -> Result<Manifest, Vec<Diagnostic>> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut manifest = Manifest::default();
+
+        let entries = Parser { chars: src.chars().collect(), i: 0, line: 1, col: 0, file_id }.parse()?;
+
+        for (index, entry) in entries.iter().enumerate() {
+            let error = |message: String| Diagnostic::new(file_id, entry.line, entry.col_start, entry.col_end, message);
+
+            if entries[..index].iter().any(|e| e.section == entry.section && e.key == entry.key) {
+                errors.push(error(format!("`{}` is given more than once", entry.key)));
+                continue;
+            }
+
+            let res = match (entry.section.as_str(), entry.key.as_str()) {
+                ("build", "entry") => strings(&entry.value).map(|v| manifest.entries = v),
+                ("build", "link") => strings(&entry.value).map(|v| manifest.link_paths = v),
+                ("build", "bundle") => strings(&entry.value).map(|v| manifest.bundle = v),
+                ("build", "runtime") => strings(&entry.value).map(|v| manifest.runtime = v),
+                ("build", "strict") => match &entry.value {
+                    Value::BOOL(b) => Ok(manifest.strict = *b),
+                    v => Err(format!("expected a boolean, got {}", v.describe())),
+                },
+                ("build", "output") => match &entry.value {
+                    Value::STRING(s) => Ok(manifest.output = Some(s.clone())),
+                    v => Err(format!("expected a string, got {}", v.describe())),
+                },
+                ("defines", name) => match &entry.value {
+                    Value::STRING(s) => Ok(manifest.defines.push((name.to_string(), format!("{s:?}")))),
+                    Value::NUMBER(n) => Ok(manifest.defines.push((name.to_string(), n.clone()))),
+                    Value::BOOL(b) => Ok(manifest.defines.push((name.to_string(), String::from(if *b { "1" } else { "0" })))),
+                    v => Err(format!("expected a string, number or boolean, got {}", v.describe())),
+                },
+                ("build", key) => Err(format!("unknown key `{key}` in `[build]`")),
+                ("", key) => Err(format!("`{key}` has to be in a section, like `[build]`")),
+                (section, _) => Err(format!("unknown section `[{section}]`")),
+            };
+
+            if let Err(e) = res {
+                errors.push(error(e));
+            }
+        }
+
+        for entry in &manifest.entries {
+            if !entry.ends_with(".rasm") {
+                errors.push(Diagnostic::new(file_id, 0, 0, 0, format!("expected a `.rasm` file as entry point, got `{entry}`")));
+            }
+        }
+
+        if manifest.output.is_some() {
+            let names: Vec<&str> = manifest.entries.iter().map(|e| e.rsplit(|c| c == '/' || c == '\\').next().unwrap()).collect();
+
+            for (i, name) in names.iter().enumerate() {
+                if names[..i].contains(name) {
+                    errors.push(Diagnostic::new(file_id, 0, 0, 0, format!("more than one entry point is named `{name}`, their outputs would overwrite each other")));
+                }
+            }
+        }
+
+        if manifest.bundle.len() > 0 && manifest.output.is_none() {
+            errors.push(Diagnostic::new(file_id, 0, 0, 0, "`bundle` needs an `output` folder to copy the libraries into"));
+        }
+
+        if manifest.entries.len() == 0 && errors.len() == 0 {
+            errors.push(Diagnostic::new(file_id, 0, 0, 0, "no entry points").with_help("add `entry = [\"main.rasm\"]` to `[build]`"));
+        }
+
+        if errors.len() > 0 {
+            return Err(errors);
+        }
+
+        return Ok(manifest);
+    }
+}
+
+// an array of strings, or a single string
+fn strings(value: &Value) -> Result<Vec<String>, String> {
+    let values = match value {
+        Value::ARRAY(values) => values.clone(),
+        value => vec![value.clone()],
+    };
+
+    let mut res = Vec::new();
+    for value in values {
+        match value {
+            Value::STRING(s) => res.push(s),
+            v => return Err(format!("expected strings, got {}", v.describe())),
+        }
+    }
+
+    return Ok(res);
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+    line: usize,
+    col: usize,
+    file_id: usize,
+}
+
+impl Parser {
+    fn parse(&mut self) -> Result<Vec<Entry>, Diagnostic> {
+        let mut res = Vec::new();
+        let mut section = String::new();
+
+        loop {
+            self.skip(true);
+
+            let c = match self.peek() {
+                Some(c) => c,
+                None => break,
+            };
+
+            if c == '[' {
+                self.bump();
+                self.skip(false);
+                section = self.key()?;
+                self.skip(false);
+                self.expect(']')?;
+            } else {
+                let (line, col_start) = (self.line, self.col);
+                let key = self.key()?;
+                let col_end = self.col;
+
+                self.skip(false);
+                self.expect('=')?;
+                self.skip(false);
+                let value = self.value()?;
+
+                res.push(Entry { section: section.clone(), key, value, line, col_start, col_end });
+            }
+
+            // only a comment can follow on the same line
+            self.skip(false);
+            match self.peek() {
+                None | Some('\n') => {}
+                Some(_) => return Err(self.error("expected the end of the line")),
+            }
+        }
+
+        return Ok(res);
+    }
+
+    // a bare key, or one between quotes
+    fn key(&mut self) -> Result<String, Diagnostic> {
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+
+        let mut res = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-') {
+            res.push(c);
+            self.bump();
+        }
+
+        if res.len() == 0 {
+            return Err(self.error("expected a name"));
+        }
+
+        return Ok(res);
+    }
+
+    fn value(&mut self) -> Result<Value, Diagnostic> {
+        match self.peek() {
+            Some('"') => return Ok(Value::STRING(self.string()?)),
+            Some('[') => {
+                self.bump();
+
+                let mut values = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.peek() == Some(']') {
+                        break;
+                    }
+
+                    values.push(self.value()?);
+
+                    self.skip(true);
+                    match self.peek() {
+                        Some(',') => self.bump(),
+                        Some(']') => break,
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+
+                self.bump();
+                return Ok(Value::ARRAY(values));
+            }
+            _ => {}
+        }
+
+        let start = self.i;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || "+-._".contains(c)) {
+            self.bump();
+        }
+
+        let word: String = self.chars[start..self.i].iter().collect();
+        return match word.as_str() {
+            "true" => Ok(Value::BOOL(true)),
+            "false" => Ok(Value::BOOL(false)),
+            _ if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') => Ok(Value::NUMBER(word)),
+            "" => Err(self.error("expected a value")),
+            _ => Err(self.error(format!("unexpected `{word}`")).with_help("strings have to be between double quotes")),
+        };
+    }
+
+    fn string(&mut self) -> Result<String, Diagnostic> {
+        self.bump();
+
+        let mut res = String::new();
+        loop {
+            let c = match self.peek() {
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            self.bump();
+
+            match c {
+                '"' => return Ok(res),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        _ => return Err(self.error("unknown escape")),
+                    };
+                    self.bump();
+                    res.push(escaped);
+                }
+                c => res.push(c),
+            }
+        }
+    }
+
+    // skips spaces and comments, and new lines too if `lines` is set
+    fn skip(&mut self, lines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => self.bump(),
+                '\n' if lines => self.bump(),
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Diagnostic> {
+        if self.peek() != Some(c) {
+            return Err(self.error(format!("expected `{c}`")));
+        }
+
+        self.bump();
+        return Ok(());
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.i).copied()
+    }
+
+    fn bump(&mut self) {
+        if self.peek() == Some('\n') {
+            self.line += 1;
+            self.col = 0;
+        } else {
+            self.col += 1;
+        }
+
+        self.i += 1;
+    }
+
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.file_id, self.line, self.col, self.col + 1, message)
+    }
+}
@@ -1,12 +1,42 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
-use std::num::ParseFloatError;
+use std::num::IntErrorKind;
+use std::collections::HashMap;
+use half::f16;
+use lazy_static::lazy_static;
+
+use crate::tokenizer::Type;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Number {
     SIGNED(i64),
     UNSIGNED(u64),
     DECIMAL(f64),
+    TYPED(Type, Box<Number>), // a literal with a suffix like `1u8`, the value always fits the type
+}
+
+lazy_static! {
+    static ref SUFFIX_MAP: HashMap<&'static str, Type> = {
+        let mut m = HashMap::new();
+        m.insert("i8", Type::I8);
+        m.insert("i16", Type::I16);
+        m.insert("i32", Type::I32);
+        m.insert("i64", Type::I64);
+        m.insert("u8", Type::U8);
+        m.insert("u16", Type::U16);
+        m.insert("u32", Type::U32);
+        m.insert("u64", Type::U64);
+        m.insert("f16", Type::F16);
+        m.insert("f32", Type::F32);
+        m.insert("f64", Type::F64);
+        m
+    };
+}
+
+pub fn suffix_name(typ: &Type) -> &'static str {
+    SUFFIX_MAP.iter().find(|(_, t)| *t == typ).map(|(s, _)| *s).unwrap_or("?")
 }
 
 impl Hash for Number {
@@ -15,68 +45,322 @@ impl Hash for Number {
             Number::SIGNED(n) => (*n).hash(state),
             Number::UNSIGNED(n) => (*n).hash(state),
             Number::DECIMAL(n) => (*n).to_bits().hash(state),
+            Number::TYPED(t, n) => {
+                t.hash(state);
+                n.hash(state);
+            }
         }
     }
 }
 
 impl Eq for Number {}
 
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::SIGNED(n) => write!(f, "{n}"),
+            Number::UNSIGNED(n) => write!(f, "{n}"),
+            Number::DECIMAL(n) => write!(f, "{n}"),
+            Number::TYPED(t, n) => write!(f, "{n}{}", suffix_name(t)),
+        }
+    }
+}
+
+macro_rules! arith {
+    ($op:ident, $checked:ident, $wrapping:ident, $saturating:ident) => {
+        pub fn $checked(&self, other: &Number) -> Result<Number, NumberError> {
+            self.apply(other, Operation::$op, Overflow::CHECKED)
+        }
+
+        pub fn $wrapping(&self, other: &Number) -> Result<Number, NumberError> {
+            self.apply(other, Operation::$op, Overflow::WRAPPING)
+        }
+
+        pub fn $saturating(&self, other: &Number) -> Result<Number, NumberError> {
+            self.apply(other, Operation::$op, Overflow::SATURATING)
+        }
+    };
+}
+
 impl Number {
-    pub fn add(&self, other: &Number) -> Number {
-        match (self, other) {
-            (Number::SIGNED(a), Number::SIGNED(b)) => Number::SIGNED(a + b),
-            (Number::UNSIGNED(a), Number::UNSIGNED(b)) => Number::UNSIGNED(a + b),
-            (Number::DECIMAL(a), Number::DECIMAL(b)) => Number::DECIMAL(a + b),
-            (Number::SIGNED(a), Number::UNSIGNED(b)) => Number::SIGNED(*a + *b as i64),
-            (Number::UNSIGNED(a), Number::SIGNED(b)) => Number::SIGNED(*a as i64 + *b),
-            (Number::SIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 + b),
-            (Number::UNSIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 + b),
-            (Number::DECIMAL(a), Number::SIGNED(b)) => Number::DECIMAL(a + *b as f64),
-            (Number::DECIMAL(a), Number::UNSIGNED(b)) => Number::DECIMAL(a + *b as f64),
-        }
-    }
-
-    pub fn sub(&self, other: &Number) -> Number {
-        match (self, other) {
-            (Number::SIGNED(a), Number::SIGNED(b)) => Number::SIGNED(a - b),
-            (Number::UNSIGNED(a), Number::UNSIGNED(b)) => Number::UNSIGNED(a - b),
-            (Number::DECIMAL(a), Number::DECIMAL(b)) => Number::DECIMAL(a - b),
-            (Number::SIGNED(a), Number::UNSIGNED(b)) => Number::SIGNED(*a - *b as i64),
-            (Number::UNSIGNED(a), Number::SIGNED(b)) => Number::SIGNED(*a as i64 - *b),
-            (Number::SIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 - b),
-            (Number::UNSIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 - b),
-            (Number::DECIMAL(a), Number::SIGNED(b)) => Number::DECIMAL(a - *b as f64),
-            (Number::DECIMAL(a), Number::UNSIGNED(b)) => Number::DECIMAL(a - *b as f64),
-        }
-    }
-
-    pub fn mul(&self, other: &Number) -> Number {
-        match (self, other) {
-            (Number::SIGNED(a), Number::SIGNED(b)) => Number::SIGNED(a * b),
-            (Number::UNSIGNED(a), Number::UNSIGNED(b)) => Number::UNSIGNED(a * b),
-            (Number::DECIMAL(a), Number::DECIMAL(b)) => Number::DECIMAL(a * b),
-            (Number::SIGNED(a), Number::UNSIGNED(b)) => Number::SIGNED(*a * *b as i64),
-            (Number::UNSIGNED(a), Number::SIGNED(b)) => Number::SIGNED(*a as i64 * *b),
-            (Number::SIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 * b),
-            (Number::UNSIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 * b),
-            (Number::DECIMAL(a), Number::SIGNED(b)) => Number::DECIMAL(a * *b as f64),
-            (Number::DECIMAL(a), Number::UNSIGNED(b)) => Number::DECIMAL(a * *b as f64),
-        }
-    }
-
-    pub fn div(&self, other: &Number) -> Number {
-        match (self, other) {
-            (Number::SIGNED(a), Number::SIGNED(b)) => Number::SIGNED(a / b),
-            (Number::UNSIGNED(a), Number::UNSIGNED(b)) => Number::UNSIGNED(a / b),
-            (Number::DECIMAL(a), Number::DECIMAL(b)) => Number::DECIMAL(a / b),
-            (Number::SIGNED(a), Number::UNSIGNED(b)) => Number::SIGNED(*a / *b as i64),
-            (Number::UNSIGNED(a), Number::SIGNED(b)) => Number::SIGNED(*a as i64 / *b),
-            (Number::SIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 / b),
-            (Number::UNSIGNED(a), Number::DECIMAL(b)) => Number::DECIMAL(*a as f64 / b),
-            (Number::DECIMAL(a), Number::SIGNED(b)) => Number::DECIMAL(a / *b as f64),
-            (Number::DECIMAL(a), Number::UNSIGNED(b)) => Number::DECIMAL(a / *b as f64),
+    // the number without its suffix type
+    pub fn value(&self) -> &Number {
+        match self {
+            Number::TYPED(_, n) => n.value(),
+            n => n,
+        }
+    }
+
+    pub fn typ(&self) -> Option<&Type> {
+        match self {
+            Number::TYPED(t, _) => Some(t),
+            _ => None,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self.value(), Number::DECIMAL(_))
+    }
+
+    fn to_i128(&self) -> i128 {
+        match self.value() {
+            Number::SIGNED(i) => *i as i128,
+            Number::UNSIGNED(u) => *u as i128,
+            n => f64::from(n.clone()) as i128,
+        }
+    }
+
+    // `+ - * / %` with the given overflow behaviour
+    pub fn apply(&self, other: &Number, op: Operation, overflow: Overflow) -> Result<Number, NumberError> {
+        let (typ, typed) = common_type(self, other)?;
+
+        if is_float(&typ) {
+            let (a, b) = (f64::from(self.clone()), f64::from(other.clone()));
+
+            if matches!(op, Operation::DIV | Operation::REM) && b == 0.0 {
+                return Err(NumberError::DIVIDE_BY_ZERO);
+            }
+
+            let res = match op {
+                Operation::ADD => a + b,
+                Operation::SUB => a - b,
+                Operation::MUL => a * b,
+                Operation::DIV => a / b,
+                Operation::REM => a % b,
+            };
+
+            return finish_float(res, typ, typed, overflow);
+        }
+
+        let (a, b) = (self.to_i128(), other.to_i128());
+
+        if matches!(op, Operation::DIV | Operation::REM) && b == 0 {
+            return Err(NumberError::DIVIDE_BY_ZERO);
+        }
+
+        let res = match op {
+            Operation::ADD => a + b,
+            Operation::SUB => a - b,
+            // two 64 bit values can overflow an i128 when multiplied
+            Operation::MUL => match a.checked_mul(b) {
+                Some(res) => res,
+                None if overflow == Overflow::WRAPPING => a.wrapping_mul(b),
+                None if (a < 0) != (b < 0) => i128::MIN,
+                None => i128::MAX,
+            },
+            Operation::DIV => a / b,
+            Operation::REM => a % b,
+        };
+
+        return finish_int(res, typ, typed, overflow);
+    }
+
+    arith!(ADD, add, wrapping_add, saturating_add);
+    arith!(SUB, sub, wrapping_sub, saturating_sub);
+    arith!(MUL, mul, wrapping_mul, saturating_mul);
+    arith!(DIV, div, wrapping_div, saturating_div);
+    arith!(REM, rem, wrapping_rem, saturating_rem);
+
+    // bitwise operations can not overflow, wrapping reinterprets the bits of the result in the common type
+    // like `u64::MAX & -1` being `-1` as an `i64`
+    pub fn and(&self, other: &Number) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, other)?;
+        return finish_int(self.to_i128() & other.to_i128(), typ, typed, Overflow::WRAPPING);
+    }
+
+    pub fn or(&self, other: &Number) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, other)?;
+        return finish_int(self.to_i128() | other.to_i128(), typ, typed, Overflow::WRAPPING);
+    }
+
+    pub fn xor(&self, other: &Number) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, other)?;
+        return finish_int(self.to_i128() ^ other.to_i128(), typ, typed, Overflow::WRAPPING);
+    }
+
+    pub fn not(&self) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, self)?;
+
+        // wrapping keeps only the bits of the type, which flips an unsigned value within its width
+        return finish_int(!self.to_i128(), typ, typed, Overflow::WRAPPING);
+    }
+
+    // bits shifted past the width of the type are dropped, like `wrapping_shl`
+    pub fn shl(&self, amount: &Number) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, self)?;
+        let amount = shift_amount(amount, &typ)?;
+
+        return finish_int(self.to_i128().wrapping_shl(amount), typ, typed, Overflow::WRAPPING);
+    }
+
+    // arithmetic for signed numbers and logical for unsigned ones
+    pub fn shr(&self, amount: &Number) -> Result<Number, NumberError> {
+        let (typ, typed) = common_int_type(self, self)?;
+        let amount = shift_amount(amount, &typ)?;
+
+        return finish_int(self.to_i128() >> amount, typ, typed, Overflow::WRAPPING);
+    }
+
+    // compares the values regardless of signedness or suffix, `None` if either is NaN
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        if self.is_float() || other.is_float() {
+            return f64::from(self.clone()).partial_cmp(&f64::from(other.clone()));
+        }
+
+        return Some(self.to_i128().cmp(&other.to_i128()));
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operation {
+    ADD,
+    SUB,
+    MUL,
+    DIV,
+    REM,
+}
+
+// what happens when a result does not fit in its type
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Overflow {
+    CHECKED,
+    WRAPPING,
+    SATURATING,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[allow(non_camel_case_types)]
+pub enum NumberError {
+    OVERFLOW(Type),
+    DIVIDE_BY_ZERO,
+    NOT_AN_INTEGER,
+    INVALID_SHIFT(Type),
+    TYPE_MISMATCH(Type, Type),
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberError::OVERFLOW(t) => write!(f, "result does not fit in a `{}`", suffix_name(t)),
+            NumberError::DIVIDE_BY_ZERO => write!(f, "division by zero"),
+            NumberError::NOT_AN_INTEGER => write!(f, "expected an integer"),
+            NumberError::INVALID_SHIFT(t) => write!(f, "shift amount must be between 0 and {} for a `{}`", bits(t) - 1, suffix_name(t)),
+            NumberError::TYPE_MISMATCH(a, b) => write!(f, "mismatched types `{}` and `{}`", suffix_name(a), suffix_name(b)),
+        }
+    }
+}
+
+fn is_float(typ: &Type) -> bool {
+    matches!(typ, Type::F16 | Type::F32 | Type::F64)
+}
+
+fn bits(typ: &Type) -> u32 {
+    match typ {
+        Type::I8 | Type::U8 => 8,
+        Type::I16 | Type::U16 | Type::F16 => 16,
+        Type::I32 | Type::U32 | Type::F32 => 32,
+        _ => 64,
+    }
+}
+
+// the type both operands are converted to, and whether the result keeps it as a suffix
+// untyped integers are `u64` if both are unsigned and `i64` otherwise
+fn common_type(a: &Number, b: &Number) -> Result<(Type, bool), NumberError> {
+    let typ = match (a.typ(), b.typ()) {
+        (Some(x), Some(y)) if x != y => return Err(NumberError::TYPE_MISMATCH(x.clone(), y.clone())),
+        (Some(t), _) | (_, Some(t)) => Some(t.clone()),
+        _ => None,
+    };
+
+    let float = a.is_float() || b.is_float();
+
+    return match typ {
+        Some(t) if float && !is_float(&t) => Err(NumberError::TYPE_MISMATCH(t, Type::F64)),
+        Some(t) => Ok((t, true)),
+        None if float => Ok((Type::F64, false)),
+        None => match (a.value(), b.value()) {
+            (Number::UNSIGNED(_), Number::UNSIGNED(_)) => Ok((Type::U64, false)),
+            _ => Ok((Type::I64, false)),
+        },
+    };
+}
+
+fn common_int_type(a: &Number, b: &Number) -> Result<(Type, bool), NumberError> {
+    let (typ, typed) = common_type(a, b)?;
+
+    if is_float(&typ) {
+        return Err(NumberError::NOT_AN_INTEGER);
+    }
+
+    return Ok((typ, typed));
+}
+
+fn shift_amount(amount: &Number, typ: &Type) -> Result<u32, NumberError> {
+    if amount.is_float() {
+        return Err(NumberError::NOT_AN_INTEGER);
+    }
+
+    return match u32::try_from(amount.to_i128()) {
+        Ok(n) if n < bits(typ) => Ok(n),
+        _ => Err(NumberError::INVALID_SHIFT(typ.clone())),
+    };
+}
+
+// fits an integer result into its type
+fn finish_int(n: i128, typ: Type, typed: bool, overflow: Overflow) -> Result<Number, NumberError> {
+    let bits = bits(&typ);
+    let signed = matches!(typ, Type::I8 | Type::I16 | Type::I32 | Type::I64);
+
+    let (min, max) = match signed {
+        true => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+        false => (0, (1i128 << bits) - 1),
+    };
+
+    let n = if n >= min && n <= max {
+        n
+    } else {
+        match overflow {
+            Overflow::CHECKED => return Err(NumberError::OVERFLOW(typ)),
+            Overflow::SATURATING => n.clamp(min, max),
+            Overflow::WRAPPING => {
+                let n = n & ((1i128 << bits) - 1);
+                if signed && n > max { n - (1i128 << bits) } else { n }
+            }
+        }
+    };
+
+    let n = if signed { Number::SIGNED(n as i64) } else { Number::UNSIGNED(n as u64) };
+
+    if typed {
+        return Ok(Number::TYPED(typ, Box::new(n)));
+    }
+
+    return Ok(n);
+}
+
+// floats have nothing to wrap around to, so wrapping leaves infinities as they are
+fn finish_float(n: f64, typ: Type, typed: bool, overflow: Overflow) -> Result<Number, NumberError> {
+    let max = match typ {
+        Type::F16 => f16::MAX.to_f64(),
+        Type::F32 => f32::MAX as f64,
+        _ => f64::MAX,
+    };
+
+    let n = if n.abs() <= max {
+        n
+    } else {
+        match overflow {
+            Overflow::CHECKED => return Err(NumberError::OVERFLOW(typ)),
+            Overflow::SATURATING => n.clamp(-max, max),
+            Overflow::WRAPPING => n,
         }
+    };
+
+    if typed {
+        return Ok(Number::TYPED(typ, Box::new(Number::DECIMAL(n))));
     }
+
+    return Ok(Number::DECIMAL(n));
 }
 
 impl From<i64> for Number {
@@ -103,6 +387,7 @@ impl From<Number> for i64 {
             Number::SIGNED(i) => i,
             Number::UNSIGNED(u) => u as i64,
             Number::DECIMAL(f) => f as i64,
+            Number::TYPED(_, n) => i64::from(*n),
         }
     }
 }
@@ -113,6 +398,7 @@ impl From<Number> for u64 {
             Number::SIGNED(i) => i as u64,
             Number::UNSIGNED(u) => u,
             Number::DECIMAL(f) => f as u64,
+            Number::TYPED(_, n) => u64::from(*n),
         }
     }
 }
@@ -123,22 +409,245 @@ impl From<Number> for f64 {
             Number::SIGNED(i) => i as f64,
             Number::UNSIGNED(u) => u as f64,
             Number::DECIMAL(f) => f,
+            Number::TYPED(_, n) => f64::from(*n),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[allow(non_camel_case_types)]
+pub enum ParseNumberError {
+    INVALID,
+    OUT_OF_RANGE,
+    MISPLACED_SEPARATOR,
+    DOES_NOT_FIT(Type),
+    EXPECTED_INTEGER(Type),
+}
+
+impl fmt::Display for ParseNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseNumberError::INVALID => write!(f, "invalid number literal"),
+            ParseNumberError::OUT_OF_RANGE => write!(f, "number literal out of range"),
+            ParseNumberError::MISPLACED_SEPARATOR => write!(f, "`_` may only be used between two digits"),
+            ParseNumberError::DOES_NOT_FIT(t) => write!(f, "literal does not fit in a `{}`", suffix_name(t)),
+            ParseNumberError::EXPECTED_INTEGER(t) => write!(f, "`{}` literals must be integers", suffix_name(t)),
+        }
+    }
+}
+
+// `_` separators have to sit between two digits of the literal's base
+fn strip_separators(s: &str, radix: u32) -> Result<String, ParseNumberError> {
+    let chars: Vec<char> = s.chars().collect();
+
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            let before = i > 0 && chars[i - 1].is_digit(radix);
+            let after = i + 1 < chars.len() && chars[i + 1].is_digit(radix);
+
+            if !before || !after {
+                return Err(ParseNumberError::MISPLACED_SEPARATOR);
+            }
+        }
+    }
+
+    return Ok(s.replace("_", ""));
+}
+
+fn parse_int(digits: &str, radix: u32, negative: bool) -> Result<Number, ParseNumberError> {
+    let u = u64::from_str_radix(digits, radix).map_err(|e| {
+        match e.kind() {
+            IntErrorKind::PosOverflow => ParseNumberError::OUT_OF_RANGE,
+            _ => ParseNumberError::INVALID,
         }
+    })?;
+
+    if !negative {
+        return Ok(Number::UNSIGNED(u));
     }
+
+    return match i64::try_from(-(u as i128)) {
+        Ok(i) => Ok(Number::SIGNED(i)),
+        Err(_) => Err(ParseNumberError::OUT_OF_RANGE),
+    };
 }
 
+// checks that `n` fits in `typ` and converts it to the matching kind of number
+fn with_type(n: Number, typ: Type) -> Result<Number, ParseNumberError> {
+    let value = match (&typ, &n) {
+        (Type::F16 | Type::F32 | Type::F64, _) => {
+            let f = f64::from(n.clone());
+            let fits = match typ {
+                Type::F16 => f.abs() <= f16::MAX.to_f64(),
+                Type::F32 => f.abs() <= f32::MAX as f64,
+                _ => true,
+            };
+
+            if !fits {
+                return Err(ParseNumberError::DOES_NOT_FIT(typ));
+            }
+
+            Number::DECIMAL(f)
+        }
+        (_, Number::DECIMAL(_)) => return Err(ParseNumberError::EXPECTED_INTEGER(typ)),
+        _ => {
+            let i = match n {
+                Number::SIGNED(i) => i as i128,
+                Number::UNSIGNED(u) => u as i128,
+                _ => unreachable!(),
+            };
+
+            let (min, max, signed) = match typ {
+                Type::I8 => (i8::MIN as i128, i8::MAX as i128, true),
+                Type::I16 => (i16::MIN as i128, i16::MAX as i128, true),
+                Type::I32 => (i32::MIN as i128, i32::MAX as i128, true),
+                Type::I64 => (i64::MIN as i128, i64::MAX as i128, true),
+                Type::U8 => (0, u8::MAX as i128, false),
+                Type::U16 => (0, u16::MAX as i128, false),
+                Type::U32 => (0, u32::MAX as i128, false),
+                _ => (0, u64::MAX as i128, false),
+            };
+
+            if i < min || i > max {
+                return Err(ParseNumberError::DOES_NOT_FIT(typ));
+            }
+
+            if signed { Number::SIGNED(i as i64) } else { Number::UNSIGNED(i as u64) }
+        }
+    };
+
+    return Ok(Number::TYPED(typ, Box::new(value)));
+}
+
+// decimal, `0x` hex, `0b` binary and `0o` octal integers, and decimals with an optional exponent,
+// any of which can end in a type suffix like `u8` or `f32`
 impl FromStr for Number {
-    type Err = ParseFloatError;
+    type Err = ParseNumberError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(u) = s.parse::<u64>() {
-            Ok(Number::UNSIGNED(u))
-        } else if let Ok(i) = s.parse::<i64>() {
-            Ok(Number::SIGNED(i))
-        } else if let Ok(f) = s.parse::<f64>() {
-            Ok(Number::DECIMAL(f))
-        } else {
-            Err(s.parse::<f64>().unwrap_err())
+        // hex digits include `f`, so hex literals can only take integer suffixes
+        let is_hex = s.trim_start_matches('-').get(..2).map_or(false, |p| p.eq_ignore_ascii_case("0x"));
+
+        let suffix = SUFFIX_MAP.iter().find(|(suffix, typ)| {
+            s.len() > suffix.len() && s.ends_with(*suffix) && !(is_hex && matches!(typ, Type::F16 | Type::F32 | Type::F64))
+        });
+
+        if let Some((suffix, typ)) = suffix {
+            return match Number::from_str(&s[..s.len() - suffix.len()])? {
+                Number::TYPED(..) => Err(ParseNumberError::INVALID),
+                n => with_type(n, typ.clone()),
+            };
+        }
+
+        let (negative, body) = match s.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, s),
+        };
+
+        let radix = match body.get(..2) {
+            Some("0x") | Some("0X") => 16,
+            Some("0b") | Some("0B") => 2,
+            Some("0o") | Some("0O") => 8,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            let digits = strip_separators(&body[2..], radix)?;
+            return parse_int(&digits, radix, negative);
         }
+
+        let body = strip_separators(body, 10)?;
+        if !body.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(ParseNumberError::INVALID);
+        }
+
+        if body.contains(|c| c == '.' || c == 'e' || c == 'E') {
+            let f = body.parse::<f64>().map_err(|_| ParseNumberError::INVALID)?;
+
+            if f.is_infinite() {
+                return Err(ParseNumberError::OUT_OF_RANGE);
+            }
+
+            return Ok(Number::DECIMAL(if negative { -f } else { f }));
+        }
+
+        return parse_int(&body, 10, negative);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(typ: Type, n: Number) -> Number {
+        Number::TYPED(typ, Box::new(n))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unsigned_underflow() {
+        let (a, b) = (Number::UNSIGNED(0), Number::UNSIGNED(1));
+
+        assert_eq!(a.sub(&b), Err(NumberError::OVERFLOW(Type::U64)));
+        assert_eq!(a.wrapping_sub(&b), Ok(Number::UNSIGNED(u64::MAX)));
+        assert_eq!(a.saturating_sub(&b), Ok(Number::UNSIGNED(0)));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(Number::SIGNED(1).div(&Number::SIGNED(0)), Err(NumberError::DIVIDE_BY_ZERO));
+        assert_eq!(Number::SIGNED(1).wrapping_rem(&Number::SIGNED(0)), Err(NumberError::DIVIDE_BY_ZERO));
+        assert_eq!(Number::DECIMAL(1.0).div(&Number::DECIMAL(0.0)), Err(NumberError::DIVIDE_BY_ZERO));
+    }
+
+    #[test]
+    fn min_divided_by_minus_one() {
+        let (a, b) = (Number::SIGNED(i64::MIN), Number::SIGNED(-1));
+
+        assert_eq!(a.div(&b), Err(NumberError::OVERFLOW(Type::I64)));
+        assert_eq!(a.wrapping_div(&b), Ok(Number::SIGNED(i64::MIN)));
+        assert_eq!(a.saturating_div(&b), Ok(Number::SIGNED(i64::MAX)));
+    }
+
+    #[test]
+    fn mixed_signed_and_unsigned() {
+        assert_eq!(Number::UNSIGNED(5).add(&Number::SIGNED(-3)), Ok(Number::SIGNED(2)));
+        assert_eq!(Number::UNSIGNED(u64::MAX).add(&Number::SIGNED(0)), Err(NumberError::OVERFLOW(Type::I64)));
+        assert_eq!(Number::UNSIGNED(u64::MAX).and(&Number::SIGNED(-1)), Ok(Number::SIGNED(-1)));
+        assert_eq!(Number::UNSIGNED(u64::MAX).xor(&Number::SIGNED(0)), Ok(Number::SIGNED(-1)));
+    }
+
+    #[test]
+    fn suffixes_have_to_match() {
+        let one_u8 = typed(Type::U8, Number::UNSIGNED(1));
+
+        assert_eq!(one_u8.add(&typed(Type::I8, Number::SIGNED(1))), Err(NumberError::TYPE_MISMATCH(Type::U8, Type::I8)));
+        assert_eq!(one_u8.add(&Number::DECIMAL(0.5)), Err(NumberError::TYPE_MISMATCH(Type::U8, Type::F64)));
+        assert_eq!(typed(Type::U8, Number::UNSIGNED(255)).wrapping_add(&one_u8), Ok(typed(Type::U8, Number::UNSIGNED(0))));
+        assert_eq!(typed(Type::U8, Number::UNSIGNED(255)).add(&one_u8), Err(NumberError::OVERFLOW(Type::U8)));
+    }
+
+    #[test]
+    fn shift_bounds() {
+        let one_u8 = typed(Type::U8, Number::UNSIGNED(1));
+
+        assert_eq!(one_u8.shl(&Number::UNSIGNED(7)), Ok(typed(Type::U8, Number::UNSIGNED(128))));
+        assert_eq!(one_u8.shl(&Number::UNSIGNED(8)), Err(NumberError::INVALID_SHIFT(Type::U8)));
+        assert_eq!(one_u8.shl(&Number::SIGNED(-1)), Err(NumberError::INVALID_SHIFT(Type::U8)));
+        assert_eq!(Number::SIGNED(-8).shr(&Number::UNSIGNED(1)), Ok(Number::SIGNED(-4)));
+        assert_eq!(Number::SIGNED(1).shl(&Number::UNSIGNED(64)), Err(NumberError::INVALID_SHIFT(Type::I64)));
+    }
+
+    #[test]
+    fn float_overflow_per_width() {
+        let big_f16 = typed(Type::F16, Number::DECIMAL(60000.0));
+        assert_eq!(big_f16.add(&big_f16), Err(NumberError::OVERFLOW(Type::F16)));
+        assert_eq!(big_f16.saturating_add(&big_f16), Ok(typed(Type::F16, Number::DECIMAL(f16::MAX.to_f64()))));
+
+        let big_f32 = typed(Type::F32, Number::DECIMAL(f32::MAX as f64));
+        assert_eq!(big_f32.mul(&typed(Type::F32, Number::DECIMAL(2.0))), Err(NumberError::OVERFLOW(Type::F32)));
+
+        let big_f64 = Number::DECIMAL(f64::MAX);
+        assert_eq!(big_f64.mul(&Number::DECIMAL(2.0)), Err(NumberError::OVERFLOW(Type::F64)));
+        assert_eq!(big_f64.wrapping_mul(&Number::DECIMAL(2.0)), Ok(Number::DECIMAL(f64::INFINITY)));
+    }
+}
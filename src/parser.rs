@@ -1,11 +1,10 @@
-use std::{collections::{HashMap, HashSet}, fs, path::Path};
+use std::collections::HashMap;
 
-use crate::{assemble, expr::Expr, instruction::Instruction, number::Number, r#struct::Struct, tokenizer::{self, Token}};
+use crate::{ast::{Branch, Constant, Extern, Field, Function, If, Import, Instr, Item, Module, Name, Operand, Program, Span, Stmt, Struct, Value}, condition, data::DataValue, diagnostic::Diagnostic, instruction::Instruction, tokenizer::{Line, SpannedToken, Token, Type}};
 use lazy_static::lazy_static;
-use rainbow_wrapper::{ident, immediate, name, r#extern::Extern, generation::Arg, types::{Type, Value}, wrapper::Wrapper};
 
 lazy_static! {
-    static ref INSTR_MAP: HashMap<&'static str, Instruction> = {
+    pub static ref INSTR_MAP: HashMap<&'static str, Instruction> = {
         let mut m = HashMap::new();
         m.insert("NOP", Instruction::NOP);
         m.insert("PUSH", Instruction::PUSH);
@@ -45,608 +44,534 @@ lazy_static! {
     };
 }
 
-pub fn parse(mut tokens: Vec<Vec<Token>>, wrapper: &mut Wrapper, link_paths: &mut HashSet<String>) -> Vec<Expr> {
-    let mut i = 0;
-
-    // pre-processing
-    let mut labels: HashMap<String, usize> = HashMap::new();
-
-    // labels
-    parse_labels(&tokens, &mut labels, &mut 0);
-
-    for line in &mut tokens {
-        if line.len() > 2 {
-            let mut i = 0;
-            
-            while i < line.len() - 2 {
-                let line2 = line.clone();
-                match &line2[i] {
-                    Token::IDENT(l) => {
-                        match &line2[i + 1] {
-                            Token::DOT => {
-                                match &line2[i + 2] {
-                                    Token::IDENT(r) => {
-                                        line.remove(i);
-                                        line.remove(i);
-                                        line.remove(i);
-
-                                        line.insert(i, Token::IDENT(l.to_owned() + "." + r));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    Token::VAR(l) => {
-                        match &line2[i + 1] {
-                            Token::DOT => {
-                                match &line2[i + 2] {
-                                    Token::IDENT(r) => {
-                                        line.remove(i);
-                                        line.remove(i);
-                                        line.remove(i);
-
-                                        line.insert(i, Token::VAR(l.to_owned() + "." + r));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    _ => {}
-                }
 
-                i += 1;
-            }
-        }
+// builds the tree of a file from its tokens, where the lines break does not matter
+//
+// items    = (function | struct | extern | module | include | data | stmt)*
+// function = type name "(" (type name)* ")" block
+// struct   = ".struct" name "{" (type name)* "}"
+// extern   = ".extern" type name "(" type* ")" word string (word name)?
+// module   = ".module" name "{" items "}"
+// include  = ".include" (string | name)
+// data     = ".data" (name type (number | string | "[" number* "]"))*
+// stmt     = instr | ":" name | block | if
+// block    = "{" stmt* "}"
+// if       = ".if" condition stmt* (".elseif" condition stmt*)* (".else" stmt*)? ".end"
+// instr    = instruction operand*
+//
+// an instruction takes every number, string, variable and name after it, and types and `:label`s
+// on its own line as long as its signature has room for them, so a type or label after that starts something new
+// after an error the rest of its line is skipped, along with any block opened on it
+pub fn parse(lines: Vec<Line>) -> Result<Program, Vec<Diagnostic>> {
+    let mut tokens: Vec<SpannedToken> = Vec::new();
+    let mut line_starts: Vec<usize> = Vec::new();
+    for line in lines {
+        line_starts.push(tokens.len());
+        tokens.extend(line.tokens);
     }
 
-    for line in &mut tokens {
-        let mut i = 0;
-        while i < line.len() {
-            let line2 = line.clone();
-            match &line2[i] {
-                Token::DOT => {
-                    i += 2;
+    let mut parser = Parser { tokens, line_starts, i: 0, errors: Vec::new() };
+    let items = parser.items(false);
+
+    if parser.errors.len() > 0 {
+        return Err(parser.errors);
+    }
+
+    return Ok(Program { items });
+}
+
+struct Parser {
+    tokens: Vec<SpannedToken>,
+    // index of the first token of every line, spans can't tell this once macros are expanded
+    line_starts: Vec<usize>,
+    i: usize,
+    errors: Vec<Diagnostic>,
+}
+
+impl Parser {
+    // items up to the end of the file, or up to the `}` closing a module
+    fn items(&mut self, in_module: bool) -> Vec<Item> {
+        let mut res: Vec<Item> = Vec::new();
+
+        loop {
+            match self.peek(0) {
+                None => break,
+                Some(Token::RCURLY) if in_module => break,
+                Some(Token::RCURLY) => {
+                    let token = self.bump();
+                    self.errors.push(token.error("unexpected `}`").with_help("there is no block to close here"));
                     continue;
                 }
-                Token::TYPE(typ) => {
-                    let mut j = 0;
-                    while j < typ.len() {
-                        let t = &typ[j];
-                        match t {
-                            tokenizer::Type::STRUCT(s) => {
-                                if s.len() == 0 {
-                                    let struct_type = match &line[i+1] {
-                                        Token::IDENT(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[i+1])
-                                    };
-
-                                    let mut new_typ = typ.clone();
-
-                                    new_typ.remove(j);
-
-                                    new_typ.insert(j, tokenizer::Type::STRUCT(struct_type.clone()));
-
-                                    let new_tok = Token::TYPE(new_typ);
-                                    line.remove(i);
-                                    line.remove(i);
-                                    line.insert(i, new_tok);
-                                }
-                            }
-                            _ => {}
-                        }
-                        j += 1;
-                    }
-                }
                 _ => {}
             }
-            i += 1;
-        }
-    }
 
-    while i < tokens.len() {
-        let mut j = 0;
-        while j < tokens[i].len() {
-            if tokens[i][j] == Token::COLON {
-                match tokens[i][j + 1].clone() {
-                    Token::IDENT(s) => {
-                        if tokens[i].len() > 2 {
-                            tokens[i].remove(j);
-                            tokens[i].remove(j);
-                            
-                            tokens[i].insert(j, Token::NUMBER(Number::UNSIGNED(*labels.get(&s).expect(format!("unknown label {s}").as_str()) as u64)));
-                        } else {
-                            tokens.remove(i);
-                        }
-                    }
-                    _ => panic!("unexpected token {:?}", tokens[i][j + 1])
+            match self.item() {
+                Ok(item) => res.push(item),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover();
                 }
             }
+        }
 
-            j += 1;
+        return res;
+    }
+
+    fn item(&mut self) -> Result<Item, Diagnostic> {
+        if matches!(self.peek(0), Some(Token::TYPE(_))) {
+            return Ok(Item::FUNCTION(self.function()?));
+        }
 
-            if i >= tokens.len() {
+        match self.directive().as_deref() {
+            Some("struct") => return Ok(Item::STRUCT(self.r#struct()?)),
+            Some("include") => return Ok(Item::IMPORT(self.include()?)),
+            Some("extern") => return Ok(Item::EXTERN(self.r#extern()?)),
+            Some("module") => return Ok(Item::MODULE(self.module()?)),
+            Some("data") => return Ok(Item::DATA(self.data())),
+            _ => return Ok(Item::STMT(self.stmt()?)),
+        }
+    }
+
+    // statements up to the `}` closing the block, or up to the `.elseif`, `.else` or `.end` of an `.if` when `in_if` is set
+    fn stmts(&mut self, in_if: bool) -> Vec<Stmt> {
+        let mut res: Vec<Stmt> = Vec::new();
+
+        loop {
+            if matches!(self.peek(0), None | Some(Token::RCURLY)) {
                 break;
             }
+
+            if in_if && matches!(self.directive().as_deref(), Some("elseif" | "else" | "end")) {
+                break;
+            }
+
+            match self.stmt() {
+                Ok(stmt) => res.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover();
+                }
+            }
         }
 
-        i += 1;
+        return res;
     }
 
-    // processing
-    let mut res: Vec<Expr> = Vec::new();
-    i = 0;
-
-    while i < tokens.len() {
-        let line = &tokens[i];
-
-        // println!("{line:?}");
-
-        if line.len() > 0 {
-            match &line[0] {
-                Token::IDENT(s) => {
-                    if INSTR_MAP.contains_key(s.as_str()) {
-                        let args = line[1..].to_vec();
-                        let mut wrapped: Vec<Value> = Vec::new();
-
-                        for arg in args {
-                            wrapped.push(match arg {
-                                Token::IDENT(s) => name!(s),
-                                Token::VAR(s) => ident!(s),
-                                Token::NUMBER(n) => {
-                                    match n {
-                                        Number::SIGNED(n) => immediate!(SIGNED(n)),
-                                        Number::UNSIGNED(n) => immediate!(UNSIGNED(n)),
-                                        Number::DECIMAL(n) => immediate!(DECIMAL(n)),
-                                    }
-                                }
-                                Token::TYPE(t) => {
-                                    Value::TYPE(to_rb_type(t))
-                                }
-                                Token::STRING(s) => {
-                                    wrapper.push_string(&s);
-
-                                    Value::IDENT(Wrapper::get_string_name(&s))
-                                }
-                                _ => panic!("unexpected token {arg:?}")
-                            });
-                        }
-
-                        res.push(Expr::INSTR(INSTR_MAP.get(s.as_str()).unwrap().clone(), wrapped));
-                    } else {
-                        todo!("unhandled ident {:?}", line[0])
-                    }
-                }
-                Token::TYPE(_) => {
-                    if line.len() < 2 {
-                        panic!("unexpected token {:?}", line[0]);
-                    }
-                    
-                    match &line[1] {
-                        Token::IDENT(_) => {
-                            let mut body = vec![line.clone()];
+    fn stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        let token = self.current()?;
 
-                            body.append(&mut parse_block(&tokens, &mut i));
+        match &token.token {
+            Token::IDENT(s) if INSTR_MAP.contains_key(s.as_str()) => return Ok(Stmt::INSTR(self.instr()?)),
+            Token::IDENT(s) => return Err(token.error(format!("unknown instruction `{s}`"))),
+            Token::COLON => {
+                self.bump();
+                return Ok(Stmt::LABEL(self.name("expected a label name after `:`")?));
+            }
+            Token::LCURLY => return Ok(Stmt::SCOPE(self.block()?)),
+            Token::TYPE(_) => return Err(token.error("functions can only be defined outside of blocks")),
+            Token::DOT => {}
+            _ => return Err(self.unexpected(0)),
+        }
 
-                            res.push(parse_function(body, wrapper, link_paths));
-                        }
-                        _ => panic!("unexpected token {:?}", line[1])
-                    }
-                }
-                Token::DOT => {
-                    if line.len() < 2 {
-                        panic!("unexpected token {:?}", line[0]);
-                    }
+        let directive = match self.directive() {
+            Some(directive) => directive,
+            None => return Err(self.unexpected(1).with_help("expected a directive after `.`")),
+        };
+        let at = self.tokens[self.i + 1].clone();
 
-                    match &line[1] {
-                        Token::IDENT(s) => {
-                            // println!("{s}");
-
-                            match s.to_lowercase().as_str() {
-                                "include" => {
-                                    match &line[2] {
-                                        Token::IDENT(s) => {
-                                            res.push(Expr::IMPORT(s.clone() + ".rbb"));
-                                        }
-                                        Token::STRING(s) => {
-                                            if s.ends_with(".rasm") {
-                                                let mut import_path = String::new();
-                                                if Path::exists(Path::new(&s)) {
-                                                    import_path = s.clone();
-                                                }
-
-                                                let mut paths = HashSet::new();
-                                                for path in link_paths.clone() {
-                                                    paths.extend(get_paths(&path));
-                                                }
-                                                
-                                                for path in paths {
-                                                    if path.ends_with(s) {
-                                                        if import_path == "" {
-                                                            import_path = path;
-                                                        } else {
-                                                            panic!("ambiguous import {s}\n({import_path} and {path})");
-                                                        }
-                                                    }
-                                                }
-
-                                                if Path::exists(Path::new(&import_path)) {
-                                                    assemble(import_path, link_paths);
-                                                }
-
-                                                res.push(Expr::IMPORT(s.split(".").next().unwrap().to_string() + ".rbb"));
-                                            } else if s.ends_with(".rbb") {
-                                                wrapper.push_import(s);
-                                            } else {
-                                                wrapper.push_import(&(s.clone() + ".rbb"));
-                                            }
-                                        }
-                                        _ => panic!("unexpected token {:?}", line[2])
-                                    }
-                                }
-                                "extern" => {
-                                    let ret_type = match &line[2] {
-                                        Token::TYPE(t) => {
-                                            to_rb_type(t.clone())
-                                        }
-                                        _ => panic!("unexpected token {:?}", line[2])
-                                    };
-
-                                    let name = match &line[3] {
-                                        Token::IDENT(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[3])
-                                    }.clone();
-
-                                    let mut arg_types: Vec<Vec<Type>> = Vec::new();
-                                    let mut index = 5;
-                                    while line[index] != Token::RPAREN {
-                                        match &line[index] {
-                                            Token::TYPE(t) => arg_types.push(to_rb_type(t.clone())),
-                                            _ => panic!("unexpected token {:?}", line[index])
-                                        }
-                                        index += 1;
-                                    }
-
-                                    let file = match &line[index+2] {
-                                        Token::STRING(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[index + 2])
-                                    }.clone();
-                                    index += 2;
-
-                                    let access_name;
-
-                                    if index < line.len() - 1 {
-                                        access_name = match &line[index + 2] {
-                                            Token::IDENT(s) => s,
-                                            _ => panic!("unexpected token {:?}", line[index + 2])
-                                        }.clone();
-                                    } else {
-                                        access_name = name.clone();
-                                    }
-
-                                    res.push(Expr::EXTERN(Extern { ret_type, name, access_name, arg_types, file }));
-                                }
-                                "if" | "elseif" => {
-                                    let left = match &line[2] {
-                                        Token::IDENT(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[2])
-                                    }.clone();
-                                    
-                                    let cond = match &line[3] {
-                                        Token::IDENT(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[3])
-                                    }.clone();
-
-                                    let right = match &line[4] {
-                                        Token::IDENT(s) => s,
-                                        _ => panic!("unexpected token {:?}", line[4])
-                                    }.clone();
-
-                                    let end = get_block_body(&tokens, i);
-
-                                    let body = parse(tokens[i+1..end].to_vec(), wrapper, link_paths);
-                                    i = end - 1;
-
-                                    match s.to_lowercase().as_str() {
-                                        "if" => res.push(Expr::IF_BLOCK(left, cond, right, body)),
-                                        "elseif" => res.push(Expr::ELSEIF_BLOCK(left, cond, right, body)),
-                                        _ => unreachable!()
-                                    }
-                                }
-                                "else" => {
-                                    let end = get_block_body(&tokens, i);
-
-                                    let body = parse(tokens[i+1..end].to_vec(), wrapper, link_paths);
-
-                                    i = end - 1;
-
-                                    res.push(Expr::ELSE_BLOCK(body));
-                                }
-                                "end" => {
-                                    res.push(Expr::END_BLOCK);
-                                }
-                                "module" => {
-                                    let name = match &line[2] {
-                                        Token::IDENT(n) => n,
-                                        _ => panic!("unexpected token {:?}", line[2])
-                                    }.clone();
-
-                                    let end = get_block_body(&tokens, i);
-
-                                    let body = parse(tokens[i+1..end-1].to_vec(), wrapper, link_paths);
-                                    i = end;
-
-                                    res.push(Expr::MODULE(name, body));
-                                }
-                                _ => panic!("unexpected token {:?}", line[1])
-                            }
-                        }
-                        Token::TYPE(t) => { // because the tokenizer felt like it
-                            match t[0] {
-                                tokenizer::Type::STRUCT(_) => {
-                                    let start = i;
-
-                                    while i < tokens.len() {
-                                        if tokens[i].len() > 0 {
-                                            if tokens[i][0] == Token::RCURLY {
-                                                break;
-                                            }
-                                        }
-
-                                        i += 1;
-                                    }
-
-                                    res.push(parse_struct(tokens[start..i].to_vec()));
-                                }
-                                _ => panic!("unexpected token {:?}", line[1])
-                            }
-                        }
-                        _ => panic!("unexpected token {:?}", line[1])
-                    }
-                }
-                Token::LCURLY => {
-                    let body = parse_block(&tokens, &mut i);
+        match directive.as_str() {
+            "if" => return Ok(Stmt::IF(self.r#if()?)),
+            "elseif" | "else" | "end" => return Err(at.error(format!("`.{directive}` without a matching `.if`"))),
+            "struct" | "include" | "extern" | "module" | "data" => return Err(at.error(format!("`.{directive}` can only be used outside of blocks"))),
+            _ => return Err(at.error(format!("unknown directive `.{directive}`"))),
+        }
+    }
 
-                    res.push(Expr::SCOPE(parse(body, wrapper, link_paths)));
-                }
-                Token::RCURLY => {} // TODO: why does this create an error
-                _ => {
-                    todo!("unhandled token {:?}", line[0])
-                }
+    // an instruction and its operands, they are checked against its signature later
+    fn instr(&mut self) -> Result<Instr, Diagnostic> {
+        let name = self.name("expected an instruction")?;
+        let instruction = INSTR_MAP[name.name.as_str()].clone();
+        let max = instruction.signature().operands.len();
+
+        let mut operands: Vec<Operand> = Vec::new();
+        loop {
+            // a label or type on a later line belongs to what follows the instruction
+            let room = operands.len() < max && self.line_starts.binary_search(&self.i).is_err();
+
+            let take = match self.peek(0) {
+                Some(Token::NUMBER(_) | Token::STRING(_) | Token::VAR(_)) => true,
+                Some(Token::IDENT(s)) => !INSTR_MAP.contains_key(s.as_str()),
+                // these can also start what follows the instruction
+                Some(Token::TYPE(_)) => room && !self.is_function(),
+                Some(Token::COLON) => room && matches!(self.peek(1), Some(Token::IDENT(_))),
+                _ => false,
+            };
+
+            if !take {
+                break;
             }
+
+            operands.push(self.operand()?);
         }
 
-        // if res.len() > 0 {
-        //     println!("{:?}", res[res.len()-1]);
-        // }
+        return Ok(Instr { instruction, name, operands });
+    }
 
-        i += 1;
+    fn operand(&mut self) -> Result<Operand, Diagnostic> {
+        let token = self.bump();
+        let mut span = token.span();
+
+        let value = match token.token.clone() {
+            Token::NUMBER(n) => Value::NUMBER(n),
+            Token::STRING(s) => Value::STRING(s),
+            Token::TYPE(t) => Value::TYPE(self.struct_names(t)?),
+            Token::COLON => {
+                let name = self.name("expected a label name after `:`")?;
+                span = span.to(&name.span);
+
+                Value::LABEL(name.name)
+            }
+            Token::IDENT(s) => Value::NAME(self.path(s, &mut span)),
+            Token::VAR(s) => Value::VAR(self.path(s, &mut span)),
+            t => return Err(token.error(format!("unexpected token {t:?}"))),
+        };
+
+        return Ok(Operand { value, span });
     }
 
-    // println!("{res:#?}");
+    // `a.b.c` for module paths and struct fields, the `.` has to touch the names on both sides
+    fn path(&mut self, mut res: String, span: &mut Span) -> String {
+        while self.i + 1 < self.tokens.len() {
+            let (prev, dot, next) = (&self.tokens[self.i - 1], &self.tokens[self.i], &self.tokens[self.i + 1]);
 
-    return res;
-}
+            let name = match &next.token {
+                Token::IDENT(s) if dot.token == Token::DOT && touches(prev, dot) && touches(dot, next) => s.clone(),
+                _ => break,
+            };
 
-fn parse_struct(tokens: Vec<Vec<Token>>) -> Expr {
-    let name = match &tokens[0][2] {
-        Token::IDENT(s) => s,
-        _ => panic!("unexpected token {:?}", tokens[0][2])
-    }.clone();
+            res = res + "." + &name;
+            *span = span.to(&next.span());
+            self.i += 2;
+        }
+
+        return res;
+    }
 
-    let mut types: Vec<Vec<tokenizer::Type>> = Vec::new();
-    let mut names: Vec<String> = Vec::new();
+    // `(return type) (name)((args)) { (body) }`
+    fn function(&mut self) -> Result<Function, Diagnostic> {
+        let ret_type = self.typ("expected a return type")?;
+        let name = self.name("expected a function name")?;
+        self.expect(Token::LPAREN, "expected `(` after the function name")?;
 
-    let mut i = 1;
-    loop {
-        match &tokens[i][0] {
-            Token::TYPE(t) => types.push(t.to_vec()),
-            _ => panic!("unexpected token {:?}", tokens[i][0])
+        let mut args: Vec<Field> = Vec::new();
+        while self.peek(0) != Some(&Token::RPAREN) {
+            let typ = self.typ("expected an argument type")?;
+            let name = self.name("expected an argument name")?;
+
+            args.push(Field { typ, name });
         }
-        match &tokens[i][1] {
-            Token::IDENT(n) => names.push(n.clone()),
-            _ => panic!("unexpected token {:?}", tokens[i][1])
+        self.bump();
+
+        let body = self.block()?;
+
+        return Ok(Function { ret_type, name, args, body });
+    }
+
+    // `{` statements `}`
+    fn block(&mut self) -> Result<Vec<Stmt>, Diagnostic> {
+        let open = self.expect(Token::LCURLY, "expected `{`")?;
+        let body = self.stmts(false);
+        self.close(&open)?;
+
+        return Ok(body);
+    }
+
+    fn r#struct(&mut self) -> Result<Struct, Diagnostic> {
+        self.i += 2;
+
+        let name = self.name("expected a struct name")?;
+        let open = self.expect(Token::LCURLY, "expected `{` after the struct name")?;
+
+        let mut fields: Vec<Field> = Vec::new();
+        while !matches!(self.peek(0), None | Some(Token::RCURLY)) {
+            let typ = self.typ("expected a field type")?;
+            let name = self.name("expected a field name")?;
+
+            fields.push(Field { typ, name });
         }
+        self.close(&open)?;
+
+        return Ok(Struct { name, fields });
+    }
+
+    fn r#extern(&mut self) -> Result<Extern, Diagnostic> {
+        self.i += 2;
 
-        i += 1;
+        let ret_type = self.typ("expected a return type")?;
+        let name = self.name("expected a function name")?;
+        self.expect(Token::LPAREN, "expected `(` after the function name")?;
 
-        if i >= tokens.len() {
-            break;
+        let mut arg_types: Vec<Vec<Type>> = Vec::new();
+        while self.peek(0) != Some(&Token::RPAREN) {
+            arg_types.push(self.typ("expected an argument type")?);
         }
+        self.bump();
+
+        // the words before the file and the access name are not checked, `from` and `as` read well
+        self.name("expected `from` followed by a library file")?;
+
+        let file = self.current().map_err(|e| e.with_help("expected a library file"))?;
+        let file_span = file.span();
+        let file = match file.token {
+            Token::STRING(s) => s,
+            _ => return Err(self.unexpected(0).with_help("expected a library file")),
+        };
+        self.bump();
 
-        if tokens[i][0] == Token::RCURLY {
-            break;
+        let mut access_name = None;
+        if matches!((self.peek(0), self.peek(1)), (Some(Token::IDENT(s)), Some(Token::IDENT(_))) if !INSTR_MAP.contains_key(s.as_str())) {
+            self.bump();
+            access_name = Some(self.name("expected an access name")?);
         }
+
+        return Ok(Extern { ret_type, name, arg_types, file, file_span, access_name });
     }
 
-    return Expr::STRUCT(Struct { name, types, names })
-}
+    fn module(&mut self) -> Result<Module, Diagnostic> {
+        self.i += 2;
 
-fn get_paths(path: &String) -> HashSet<String> {
-    let mut path_queue: Vec<String> = Vec::new();
-    let mut res = HashSet::new();
-    
-    path_queue.push(path.to_string());
-
-    while path_queue.len() > 0 {
-        let path = path_queue.remove(0);
-        let paths = match fs::read_dir(path.clone()) {
-            Ok(p) => p,
-            Err(e) => panic!("{}", e.to_string()),
-        };
+        let name = self.name("expected a module name")?;
+        let open = self.expect(Token::LCURLY, "expected `{` after the module name")?;
+        let items = self.items(true);
+        self.close(&open)?;
+
+        return Ok(Module { name, items });
+    }
 
-        for path in paths {
-            let dir_entry = path.unwrap();
+    fn include(&mut self) -> Result<Import, Diagnostic> {
+        self.i += 2;
 
-            let path = dir_entry.path().as_os_str().to_str().unwrap().to_string();
+        let token = self.current().map_err(|e| e.with_help("expected a file name"))?;
+        let (file, quoted) = match &token.token {
+            Token::IDENT(s) => (s.clone(), false),
+            Token::STRING(s) => (s.clone(), true),
+            _ => return Err(self.unexpected(0).with_help("expected a file name")),
+        };
+        self.bump();
 
-            if dir_entry.metadata().unwrap().is_dir() {
-                path_queue.push(path);
-            } else if dir_entry.metadata().unwrap().is_file() {
-                res.insert(path.replace("\\", "/"));
+        return Ok(Import { file, quoted, span: token.span() });
+    }
+
+    // constants up to the end of the file or module
+    fn data(&mut self) -> Vec<Constant> {
+        self.i += 2;
+
+        let mut res: Vec<Constant> = Vec::new();
+        while !matches!(self.peek(0), None | Some(Token::RCURLY)) {
+            match self.constant() {
+                Ok(constant) => res.push(constant),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover();
+                }
             }
         }
+
+        return res;
     }
 
-    return res;
-}
+    // name type value, where value is a number, a string or a `[...]` array of numbers
+    fn constant(&mut self) -> Result<Constant, Diagnostic> {
+        let name = self.name("expected a constant name")?;
+        let typ = self.typ("expected a type")?;
+
+        let token = self.current().map_err(|e| e.with_help("expected a number, string or array"))?;
+        let mut value_span = token.span();
+
+        let value = match token.token {
+            Token::NUMBER(n) => DataValue::SCALAR(n),
+            Token::STRING(s) => DataValue::STRING(s),
+            Token::LSQUARE => {
+                self.bump();
+
+                let mut values = Vec::new();
+                loop {
+                    match self.current()?.token {
+                        Token::RSQUARE => break,
+                        Token::NUMBER(n) => values.push(n),
+                        _ => return Err(self.unexpected(0).with_help("expected a number")),
+                    }
+                    self.bump();
+                }
+                value_span = value_span.to(&self.tokens[self.i].span());
 
-fn parse_labels(tokens: &Vec<Vec<Token>>, labels: &mut HashMap<String, usize>, i: &mut usize) {
-    let mut instr = 0;
+                DataValue::ARRAY(values)
+            }
+            _ => return Err(self.unexpected(0).with_help("expected a number, string or array")),
+        };
+        self.bump();
+
+        return Ok(Constant { name, typ, value, value_span });
+    }
+
+    // an `.if` chain, the preprocessor already removed the ones decided while assembling
+    fn r#if(&mut self) -> Result<If, Diagnostic> {
+        let start = self.tokens[self.i + 1].clone();
 
-    while *i < tokens.len() {
-        let line = &tokens[*i];
+        let mut branches: Vec<Branch> = Vec::new();
+        let mut otherwise: Option<Vec<Stmt>> = None;
 
-        if line.len() > 0 {
-            if line.contains(&Token::LCURLY) {
-                *i += 1;
-                instr += 1;
-                parse_labels(tokens, labels, i);
-                continue;
+        let mut directive = String::from("if");
+        loop {
+            let at = self.tokens[self.i + 1].clone();
+            self.i += 2;
+
+            if otherwise.is_some() && directive != "end" {
+                return Err(at.error(format!("`.{directive}` after `.else`")));
             }
 
-            match &line[0] {
-                Token::IDENT(s) => {
-                    if INSTR_MAP.contains_key(s.as_str()) {
-                        instr += 1;
-                    }
-                }
-                Token::COLON => {
-                    match &line[1] {
-                        Token::IDENT(s) => {
-                            if labels.contains_key(s) {
-                                panic!("redefined label {s}");
-                            } else {
-                                labels.insert(s.to_string(), instr);
-                            }
-                        }
-                        _ => panic!("unexpected token {:?}", line[1])
+            match directive.as_str() {
+                "if" | "elseif" => {
+                    if self.i >= self.tokens.len() {
+                        return Err(at.error("expected a condition"));
                     }
+
+                    let (condition, end) = condition::parse_tokens(&self.tokens, self.i)?;
+                    self.i = end;
+
+                    let body = self.stmts(true);
+                    branches.push(Branch { condition, body, span: at.span() });
                 }
-                Token::RCURLY => {
-                    *i += 1;
-                    break;
-                }
-                _ => {}
+                "else" => otherwise = Some(self.stmts(true)),
+                _ => break,
             }
+
+            directive = match self.directive() {
+                Some(directive) if matches!(directive.as_str(), "elseif" | "else" | "end") => directive,
+                _ => return Err(start.error("unclosed `.if`").with_help("close it with `.end`")),
+            };
         }
 
-        *i += 1;
+        return Ok(If { branches, otherwise });
+    }
+
+    // the name of the directive starting at the current token, `.struct` is tokenized as a type
+    fn directive(&self) -> Option<String> {
+        match (self.peek(0), self.peek(1)) {
+            (Some(Token::DOT), Some(Token::IDENT(s))) => Some(s.to_lowercase()),
+            (Some(Token::DOT), Some(Token::TYPE(t))) if *t == vec![Type::STRUCT(String::new())] => Some(String::from("struct")),
+            _ => None,
+        }
     }
-}
 
-fn parse_block(tokens: &Vec<Vec<Token>>, i: &mut usize) -> Vec<Vec<Token>> {
-    let mut res: Vec<Vec<Token>> = Vec::new();
+    // whether a function definition starts at the current token
+    fn is_function(&self) -> bool {
+        let name = match self.peek(0) {
+            Some(Token::TYPE(t)) if t.contains(&Type::STRUCT(String::new())) => 2,
+            Some(Token::TYPE(_)) => 1,
+            _ => return false,
+        };
 
-    *i += 1;
+        return matches!((self.peek(name), self.peek(name + 1)), (Some(Token::IDENT(_)), Some(Token::LPAREN)));
+    }
 
-    while *i < tokens.len() {
-        if tokens[*i].len() > 0 {
-            match tokens[*i][0] {
-                Token::LCURLY => {
-                    res.push(vec![Token::LCURLY]);
-                    res.append(&mut parse_block(tokens, i));
-                    res.push(vec![Token::RCURLY]);
-                }
-                Token::RCURLY => {
-                    *i += 1;
-                    break;
-                }
-                _ => res.push(tokens[*i].clone())
-            }
-        }
+    fn name(&mut self, help: &str) -> Result<Name, Diagnostic> {
+        let name = match self.peek(0) {
+            Some(Token::IDENT(s)) => s.clone(),
+            _ => return Err(self.unexpected(0).with_help(help)),
+        };
 
-        *i += 1;
+        return Ok(Name { name, span: self.bump().span() });
     }
 
-    return res;
-}
+    fn typ(&mut self, help: &str) -> Result<Vec<Type>, Diagnostic> {
+        let typ = match self.peek(0) {
+            Some(Token::TYPE(t)) => t.clone(),
+            _ => return Err(self.unexpected(0).with_help(help)),
+        };
+        self.bump();
 
-fn get_block_body(tokens: &Vec<Vec<Token>>, i: usize) -> usize {
-    let mut end = i + 1;
-    while end < tokens.len() {
-        // :)
-        if tokens[end].len() >= 2 {
-            match &tokens[end][0] {
-                Token::DOT => {
-                    match &tokens[end][1] {
-                        Token::IDENT(s) => {
-                            match s.as_str() {
-                                "if" | "elseif" | "else" | "end" => {
-                                    break;
-                                }
-                                _ => {}
-                            }
-                        }       
-                        _ => {}
-                    }
-                }
-                _ => {}
+        return self.struct_names(typ);
+    }
+
+    // `struct` in a type is followed by the name of the struct
+    fn struct_names(&mut self, mut typ: Vec<Type>) -> Result<Vec<Type>, Diagnostic> {
+        for t in typ.iter_mut() {
+            if *t == Type::STRUCT(String::new()) {
+                *t = Type::STRUCT(self.name("expected a struct name after `struct`")?.name);
             }
         }
 
-        end += 1;
+        return Ok(typ);
     }
 
-    return end;
-}
+    fn expect(&mut self, token: Token, help: &str) -> Result<SpannedToken, Diagnostic> {
+        if self.peek(0) != Some(&token) {
+            return Err(self.unexpected(0).with_help(help));
+        }
 
-pub fn to_rb_type(t: Vec<tokenizer::Type>) -> Vec<Type> {
-    let mut new_type = Vec::new();
-    for typ in t {
-        new_type.push(typ.to_rbtype());
+        return Ok(self.bump());
     }
 
-    return new_type;
-}
-
-pub fn parse_function(tokens: Vec<Vec<Token>>, wrapper: &mut Wrapper, link_paths: &mut HashSet<String>) -> Expr {
-    let ret_type = match &tokens[0][0] {
-        Token::TYPE(t) => {
-            to_rb_type(t.clone())
+    // the `}` of the block opened by `open`
+    fn close(&mut self, open: &SpannedToken) -> Result<(), Diagnostic> {
+        if self.peek(0) != Some(&Token::RCURLY) {
+            return Err(open.error("unclosed `{`").with_help("close it with `}`"));
         }
-        _ => panic!("unexpected token {:?}", tokens[0][0])
-    };
 
-    let name = match &tokens[0][1] {
-        Token::IDENT(n) => n,
-        _ => panic!("unexpected token {:?}", tokens[0][0])
-    }.clone();
-
-    let mut args: Vec<Arg> = Vec::new();
-    let mut i = 3;
-    while tokens[0][i] != Token::RPAREN {
-        let typ = match &tokens[0][i] {
-            Token::TYPE(t) => {
-                to_rb_type(t.clone())
-            }
-            _ => panic!("unexpected token {:?}", tokens[0][i])
-        };
+        self.bump();
+        return Ok(());
+    }
 
-        let name = match &tokens[0][i + 1] {
-            Token::IDENT(s) => s,
-            _ => panic!("unexpected token {:?}", tokens[0][i + 1])
-        }.clone();
+    // skips the rest of the line the parser stopped on, along with any block opened on it
+    fn recover(&mut self) {
+        let mut depth = 0;
+        let mut line = None;
 
-        args.push(Arg { name, typ });
+        while let Some(token) = self.tokens.get(self.i) {
+            let here = (token.file_id, token.line);
+            if depth == 0 && line.is_some_and(|line| line != here) {
+                break;
+            }
+
+            match token.token {
+                Token::LCURLY => depth += 1,
+                Token::RCURLY if depth == 0 => break,
+                Token::RCURLY => depth -= 1,
+                _ => {}
+            }
 
-        i += 2;
+            line = Some(here);
+            self.i += 1;
+        }
     }
 
-    let body = parse(tokens[1..].to_vec(), wrapper, link_paths);
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.i + offset).map(|t| &t.token)
+    }
 
-    return Expr::FUNCDEF(name, args, ret_type, body);
-}
+    fn bump(&mut self) -> SpannedToken {
+        self.i += 1;
+        return self.tokens[self.i - 1].clone();
+    }
 
-pub fn emit(exprs: &Vec<Expr>) -> Vec<u8> {
-    let mut res: Vec<u8> = Vec::new();
+    // the current token, or an error if the file ends before it
+    fn current(&self) -> Result<SpannedToken, Diagnostic> {
+        return self.tokens.get(self.i).cloned().ok_or_else(|| self.unexpected(0));
+    }
 
-    for expr in exprs {
-        res.append(&mut expr.to_bytes());
+    fn unexpected(&self, offset: usize) -> Diagnostic {
+        match (self.tokens.get(self.i + offset), self.tokens.last()) {
+            (Some(token), _) => token.error(format!("unexpected token {:?}", token.token)),
+            (None, Some(last)) => Diagnostic::new(last.file_id, last.line, last.col_end, last.col_end + 1, "unexpected end of file"),
+            (None, None) => Diagnostic::new(0, 0, 0, 0, "unexpected end of file"),
+        }
     }
+}
+
+// whether `b` directly follows `a`, the tokens a define was replaced with all have the span of its name
+fn touches(a: &SpannedToken, b: &SpannedToken) -> bool {
+    let same = a.col_start == b.col_start && a.col_end == b.col_end;
 
-    return res;
+    return a.file_id == b.file_id && a.line == b.line && (a.col_end == b.col_start || same);
 }
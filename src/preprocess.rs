@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{condition::{self, Comparison}, diagnostic::Diagnostic, number::Number, parser::INSTR_MAP, tokenizer::{Line, SpannedToken, Token}};
+
+// names given with `-D` or `.define`, and names whose value is only known by the runtime
+#[derive(Debug, Clone, Default)]
+pub struct Defines {
+    pub values: HashMap<String, Line>,
+    pub runtime: HashSet<String>,
+}
+
+// an `.if` chain that is currently open
+struct Frame {
+    index: usize,        // the line of the `.if`
+    runtime: bool,       // the chain is left for the runtime to decide
+    outer_active: bool,  // the lines around the chain are kept
+    active: bool,        // the current branch is kept
+    taken: bool,         // a branch has been kept already
+    has_else: bool,
+}
+
+// handles `.define`, `.runtime` and `.if` chains that can be decided while assembling, and substitutes defined names
+// lines that are removed are left empty so line numbers stay intact
+pub fn preprocess(mut tokens: Vec<Line>, mut defines: Defines) -> Result<Vec<Line>, Vec<Diagnostic>> {
+    let mut errors: Vec<Diagnostic> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+
+    for index in 0..tokens.len() {
+        let active = stack.last().map_or(true, |f| f.active);
+        let in_runtime = stack.iter().any(|f| f.runtime);
+        let line = &mut tokens[index];
+
+        let directive = match (line.get(0), line.get(1)) {
+            (Some(Token::DOT), Some(Token::IDENT(s))) => s.to_lowercase(),
+            _ => String::new(),
+        };
+
+        match directive.as_str() {
+            "if" => {
+                if !active {
+                    stack.push(Frame { index, runtime: false, outer_active: false, active: false, taken: true, has_else: false });
+                    line.clear();
+                    continue;
+                }
+
+                match evaluate(line, &defines) {
+                    Ok(Some(cond)) => {
+                        stack.push(Frame { index, runtime: false, outer_active: true, active: cond, taken: cond, has_else: false });
+                        line.clear();
+                    }
+                    Ok(None) => stack.push(Frame { index, runtime: true, outer_active: true, active: true, taken: false, has_else: false }),
+                    Err(e) => {
+                        errors.push(e);
+                        stack.push(Frame { index, runtime: false, outer_active: true, active: false, taken: true, has_else: false });
+                        line.clear();
+                    }
+                }
+            }
+            "elseif" | "else" => {
+                let frame = match stack.last_mut() {
+                    Some(frame) => frame,
+                    None => {
+                        errors.push(line.error_at(1, format!("`.{directive}` without a matching `.if`")));
+                        line.clear();
+                        continue;
+                    }
+                };
+
+                if frame.has_else {
+                    errors.push(line.error_at(1, format!("`.{directive}` after `.else`")));
+                }
+                frame.has_else |= directive == "else";
+
+                // the defines are folded into every condition of a chain left for the runtime, not only the `.if`
+                if frame.runtime {
+                    if directive == "elseif" {
+                        match evaluate(line, &defines) {
+                            Ok(None) => {}
+                            // the runtime still decides the chain, so it is given the value
+                            Ok(Some(cond)) => {
+                                let at = line.tokens[2].clone();
+                                let value = SpannedToken { token: Token::NUMBER(Number::UNSIGNED(cond as u64)), ..at.clone() };
+                                let zero = SpannedToken { token: Token::NUMBER(Number::UNSIGNED(0)), ..at };
+
+                                rewrite(line, value, Comparison::NE, zero);
+                            }
+                            Err(e) => errors.push(e),
+                        }
+                    }
+
+                    continue;
+                }
+
+                if !frame.outer_active || frame.taken {
+                    frame.active = false;
+                } else if directive == "else" {
+                    frame.active = true;
+                } else {
+                    match evaluate(line, &defines) {
+                        Ok(Some(cond)) => frame.active = cond,
+                        Ok(None) => {
+                            errors.push(line.error("this condition depends on the runtime, but the `.if` it belongs to was decided while assembling")
+                                .with_help("every condition of an `.if` chain has to be known either while assembling or by the runtime"));
+                            frame.active = false;
+                        }
+                        Err(e) => {
+                            errors.push(e);
+                            frame.active = false;
+                        }
+                    }
+                }
+
+                frame.taken |= frame.active;
+                line.clear();
+            }
+            "end" => {
+                match stack.pop() {
+                    Some(frame) if !frame.runtime => line.clear(),
+                    Some(_) => {}
+                    None => {
+                        errors.push(line.error_at(1, "`.end` without a matching `.if`"));
+                        line.clear();
+                    }
+                }
+            }
+            _ if !active => line.clear(),
+            "define" => {
+                if in_runtime {
+                    errors.push(line.error_at(1, "cannot `.define` inside of an `.if` decided by the runtime"));
+                } else {
+                    match parse_define(line) {
+                        Ok((name, value)) => {
+                            if defines.values.contains_key(&name) || defines.runtime.contains(&name) {
+                                errors.push(line.error_at(2, format!("`{name}` is already defined")));
+                            } else {
+                                defines.values.insert(name, value);
+                            }
+                        }
+                        Err(e) => errors.push(e),
+                    }
+                }
+
+                line.clear();
+            }
+            "runtime" => {
+                for i in 2..line.len() {
+                    match &line[i] {
+                        Token::IDENT(name) if defines.values.contains_key(name) => {
+                            errors.push(line.error_at(i, format!("`{name}` is already defined")));
+                        }
+                        Token::IDENT(name) => {
+                            defines.runtime.insert(name.clone());
+                        }
+                        _ => errors.push(line.error_at(i, "expected a name")),
+                    }
+                }
+
+                if line.len() < 3 {
+                    errors.push(line.error("expected at least one name after `.runtime`"));
+                }
+
+                line.clear();
+            }
+            _ => substitute(line, &defines),
+        }
+    }
+
+    for frame in stack {
+        errors.push(tokens[frame.index].error_at(1, "unclosed `.if`").with_help("close it with `.end`"));
+    }
+
+    if errors.len() > 0 {
+        return Err(errors);
+    }
+
+    return Ok(tokens);
+}
+
+// `.define NAME value...`
+fn parse_define(line: &Line) -> Result<(String, Line), Diagnostic> {
+    let name = match line.get(2) {
+        Some(Token::IDENT(s)) => s.clone(),
+        _ => return Err(line.error_at(2, "expected a name after `.define`")),
+    };
+
+    if INSTR_MAP.contains_key(name.as_str()) {
+        return Err(line.error_at(2, format!("cannot define `{name}`, it is an instruction")));
+    }
+
+    if line.len() < 4 {
+        return Err(line.error(format!("expected a value for `{name}`")));
+    }
+
+    return Ok((name, Line { tokens: line.tokens[3..].to_vec(), ..line.clone() }));
+}
+
+// replaces defined names in instruction arguments with their values, wherever the instruction is on the line
+fn substitute(line: &mut Line, defines: &Defines) {
+    if defines.values.len() == 0 {
+        return;
+    }
+
+    let mut in_args = false;
+    let mut i = 0;
+    while i < line.len() {
+        match &line[i] {
+            Token::IDENT(s) if INSTR_MAP.contains_key(s.as_str()) => {
+                in_args = true;
+                i += 1;
+                continue;
+            }
+            // a block or a function header follows the arguments
+            Token::LCURLY | Token::RCURLY | Token::LPAREN | Token::RPAREN => in_args = false,
+            _ => {}
+        }
+
+        // names next to a `.` or after a `:` are part of a module path or a label, and a name before `(` is a function
+        let attached = (i > 0 && (line[i - 1] == Token::DOT || line[i - 1] == Token::COLON))
+            || line.get(i + 1) == Some(&Token::DOT) || line.get(i + 1) == Some(&Token::LPAREN);
+
+        let value = match &line[i] {
+            Token::IDENT(s) if in_args && !attached => defines.values.get(s),
+            _ => None,
+        };
+
+        match value {
+            Some(value) => {
+                // the substituted tokens point at the name they replaced
+                let at = line.tokens[i].clone();
+                let replacement = value.tokens.iter().map(|t| SpannedToken { token: t.token.clone(), ..at.clone() });
+
+                line.tokens.splice(i..i + 1, replacement);
+                i += value.len();
+            }
+            None => i += 1,
+        }
+    }
+}
+
+// the condition of an `.if` or `.elseif`, `None` if it has to be left for the runtime
+// conditions left for the runtime are rewritten to `left op right` with every define folded in
+fn evaluate(line: &mut Line, defines: &Defines) -> Result<Option<bool>, Diagnostic> {
+    let cond = condition::parse(line, 2)?.simplify(defines)?;
+
+    if let Some(b) = cond.truth() {
+        return Ok(Some(b));
+    }
+
+    let (left, op, right) = cond.runtime_parts()?;
+    rewrite(line, left, op, right);
+
+    return Ok(None);
+}
+
+// replaces the condition of an `.if` or `.elseif` with `left op right`
+fn rewrite(line: &mut Line, left: SpannedToken, op: Comparison, right: SpannedToken) {
+    let op = SpannedToken { token: Token::IDENT(op.as_str().to_string()), ..left.clone() };
+
+    line.tokens.truncate(2);
+    line.tokens.extend([left, op, right]);
+}
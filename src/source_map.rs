@@ -0,0 +1,42 @@
+// keeps the text of every file read during a run so positions can be mapped back to it
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: String,
+    pub contents: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    // adds a file and returns its id
+    pub fn add(&mut self, path: &str, contents: String) -> usize {
+        self.files.push(SourceFile { path: path.replace("\\", "/"), contents });
+
+        return self.files.len() - 1;
+    }
+
+    pub fn get(&self, file_id: usize) -> Option<&SourceFile> {
+        self.files.get(file_id)
+    }
+
+    pub fn path(&self, file_id: usize) -> &str {
+        match self.files.get(file_id) {
+            Some(file) => &file.path,
+            None => "<unknown>",
+        }
+    }
+
+    // the text of a 1 based line, without its line ending
+    pub fn line(&self, file_id: usize, line: usize) -> Option<&str> {
+        let file = self.files.get(file_id)?;
+
+        return file.contents.split("\n").nth(line.checked_sub(1)?).map(|l| l.trim_end_matches('\r'));
+    }
+}
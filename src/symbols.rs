@@ -0,0 +1,449 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{assembler::Assembler, ast::{Field, Import, Instr, Item, Name, Program, Span, Stmt, Type, Value}, diagnostic::Diagnostic, instruction::{Instruction, Operand}};
+
+// the names a `.rbb` file makes available to the files including it, written next to it as `.rbb.decl`
+// they can be checked without reading the bytecode, and one can be written by hand for a `.rbb` that was not made by RASM
+//
+// function println
+// function fs.open
+// struct File
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Declarations {
+    pub functions: Vec<String>, // with the path of the module they are in, like `fs.open`
+    pub structs: Vec<String>,
+}
+
+impl Declarations {
+    // the functions and structs of a file, including the ones in its modules
+    pub fn of(program: &Program) -> Declarations {
+        let mut res = Declarations::default();
+        res.add(&program.items, "");
+
+        return res;
+    }
+
+    fn add(&mut self, items: &Vec<Item>, prefix: &str) {
+        for item in items {
+            match item {
+                Item::FUNCTION(function) => self.functions.push(format!("{prefix}{}", function.name.name)),
+                Item::STRUCT(s) => self.structs.push(format!("{prefix}{}", s.name.name)),
+                Item::MODULE(module) => self.add(&module.items, &format!("{prefix}{}.", module.name.name)),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut res = String::new();
+
+        for function in &self.functions {
+            res += &format!("function {function}\n");
+        }
+        for s in &self.structs {
+            res += &format!("struct {s}\n");
+        }
+
+        return res;
+    }
+
+    // lines that are empty, comments or of an unknown kind are skipped
+    pub fn parse(s: &str) -> Declarations {
+        let mut res = Declarations::default();
+
+        for line in s.lines() {
+            match line.trim().split_once(' ') {
+                Some(("function", name)) => res.functions.push(name.trim().to_string()),
+                Some(("struct", name)) => res.structs.push(name.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        return res;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+enum Kind {
+    FUNCTION, // functions and externs
+    STRUCT,
+    MODULE,
+    INCLUDE,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::FUNCTION => "function",
+            Kind::STRUCT => "struct",
+            Kind::MODULE => "module",
+            Kind::INCLUDE => "included file",
+        }
+    }
+}
+
+struct Symbol {
+    kind: Kind,
+    span: Option<Span>, // `None` for names from declarations
+}
+
+// checks that every variable, function and struct that is used is defined, and that nothing is defined twice
+// runs before emitting, once the included files were assembled and their declarations written
+pub fn check(program: &Program, asm: &mut Assembler) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = Checker { asm, symbols: HashMap::new(), opaque: HashSet::new(), scopes: Vec::new(), module: Vec::new(), errors: Vec::new() };
+
+    checker.define_items(&program.items, "");
+    checker.items(&program.items);
+
+    if checker.errors.len() > 0 {
+        return Err(checker.errors);
+    }
+
+    return Ok(());
+}
+
+struct Checker<'a> {
+    asm: &'a mut Assembler,
+    symbols: HashMap<String, Symbol>,      // every function, struct, module and included file by its path, like `m.f` or `io.println`
+    opaque: HashSet<String>,               // included files without declarations, any name can be used from them
+    scopes: Vec<HashMap<String, Span>>,    // the variables of the blocks around the current statement, innermost last
+    module: Vec<String>,                   // the modules around the current item
+    errors: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    // collects the functions, structs and modules of every module first, so they can be used before they are defined
+    fn define_items(&mut self, items: &Vec<Item>, prefix: &str) {
+        for item in items {
+            match item {
+                Item::FUNCTION(function) => self.define(prefix, &function.name, Kind::FUNCTION),
+                Item::EXTERN(e) => self.define(prefix, e.access_name.as_ref().unwrap_or(&e.name), Kind::FUNCTION),
+                Item::STRUCT(s) => {
+                    self.define(prefix, &s.name, Kind::STRUCT);
+                    self.fields(&s.fields, &format!("field `{{}}` in struct `{}`", s.name.name));
+                }
+                Item::MODULE(module) => {
+                    self.define(prefix, &module.name, Kind::MODULE);
+                    self.define_items(&module.items, &format!("{prefix}{}.", module.name.name));
+                }
+                Item::IMPORT(import) => self.import(prefix, import),
+                _ => {}
+            }
+        }
+    }
+
+    fn define(&mut self, prefix: &str, name: &Name, kind: Kind) {
+        let path = format!("{prefix}{}", name.name);
+
+        match self.symbols.get(&path) {
+            Some(symbol) => {
+                let mut error = name.span.error(format!("`{}` is already defined", name.name));
+
+                match symbol.span {
+                    Some(span) if span.file_id == name.span.file_id => error = error.with_help(format!("it was first defined as a {} on line {}", symbol.kind.as_str(), span.line)),
+                    _ => error = error.with_help(format!("it was first defined as a {}", symbol.kind.as_str())),
+                }
+
+                self.errors.push(error);
+            }
+            None => {
+                self.symbols.insert(path, Symbol { kind, span: Some(name.span) });
+            }
+        }
+    }
+
+    // the names of an included file are used as `(file name).(name)`
+    fn import(&mut self, prefix: &str, import: &Import) {
+        let name = import.file.rsplit(['/', '\\']).next().unwrap().split('.').next().unwrap();
+        let path = format!("{prefix}{name}");
+
+        match self.asm.declarations(&import.file, import.quoted) {
+            Some(declarations) => {
+                for function in declarations.functions {
+                    self.symbols.entry(format!("{path}.{function}")).or_insert(Symbol { kind: Kind::FUNCTION, span: None });
+                }
+                for s in declarations.structs {
+                    self.symbols.entry(format!("{path}.{s}")).or_insert(Symbol { kind: Kind::STRUCT, span: None });
+                }
+            }
+            None => {
+                self.opaque.insert(path.clone());
+            }
+        }
+
+        // the same file can be included more than once
+        if !matches!(self.symbols.get(&path), Some(Symbol { kind: Kind::INCLUDE, .. })) {
+            self.define(prefix, &Name { name: name.to_string(), span: import.span }, Kind::INCLUDE);
+        }
+    }
+
+    // arguments and struct fields, `what` names one of them with `{}` in place of its name
+    fn fields(&mut self, fields: &Vec<Field>, what: &str) {
+        for (i, field) in fields.iter().enumerate() {
+            if let Some(first) = fields[..i].iter().find(|f| f.name.name == field.name.name) {
+                self.errors.push(field.name.span.error(format!("duplicate {}", what.replace("{}", &field.name.name)))
+                    .with_help(format!("the first one is on line {}", first.name.span.line)));
+            }
+        }
+    }
+
+    fn items(&mut self, items: &Vec<Item>) {
+        // variables declared outside of functions can be used by every function around them
+        self.scopes.push(HashMap::new());
+        for item in items {
+            match item {
+                Item::STMT(Stmt::INSTR(instr)) => {
+                    if let Some(name) = declared(instr) {
+                        self.declare(&name);
+                    }
+                }
+                Item::DATA(constants) => {
+                    for constant in constants {
+                        self.scopes.last_mut().unwrap().insert(constant.name.name.clone(), constant.name.span);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for item in items {
+            match item {
+                Item::FUNCTION(function) => {
+                    self.typ(&function.ret_type, function.name.span);
+                    self.fields(&function.args, &format!("argument `{{}}` in function `{}`", function.name.name));
+
+                    self.scopes.push(HashMap::new());
+                    for arg in &function.args {
+                        self.typ(&arg.typ, arg.name.span);
+                        self.scopes.last_mut().unwrap().insert(arg.name.name.clone(), arg.name.span);
+                    }
+
+                    self.stmts(&function.body);
+                    self.scopes.pop();
+                }
+                Item::STRUCT(s) => {
+                    for field in &s.fields {
+                        self.typ(&field.typ, field.name.span);
+                    }
+                }
+                Item::EXTERN(e) => {
+                    self.typ(&e.ret_type, e.name.span);
+                    for typ in &e.arg_types {
+                        self.typ(typ, e.name.span);
+                    }
+                }
+                Item::MODULE(module) => {
+                    self.module.push(module.name.name.clone());
+                    self.items(&module.items);
+                    self.module.pop();
+                }
+                // the variable it declares was declared above
+                Item::STMT(Stmt::INSTR(instr)) => self.operands(instr),
+                Item::STMT(stmt) => self.stmt(stmt),
+                Item::IMPORT(_) | Item::DATA(_) => {}
+            }
+        }
+
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, stmts: &Vec<Stmt>) {
+        self.scopes.push(HashMap::new());
+        self.stmts(stmts);
+        self.scopes.pop();
+    }
+
+    // inside of a block a variable can only be used after it was declared
+    fn stmts(&mut self, stmts: &Vec<Stmt>) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::INSTR(instr) => self.instr(instr),
+            Stmt::LABEL(_) => {}
+            Stmt::SCOPE(body) => self.block(body),
+            // branches don't open a scope of their own, what they declare is seen after `.end`
+            Stmt::IF(chain) => {
+                for branch in &chain.branches {
+                    self.stmts(&branch.body);
+                }
+
+                if let Some(body) = &chain.otherwise {
+                    self.stmts(body);
+                }
+            }
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        self.operands(instr);
+
+        if let Some(name) = declared(instr) {
+            self.declare(&name);
+        }
+    }
+
+    fn operands(&mut self, instr: &Instr) {
+        let signature = instr.instruction.signature();
+
+        for (i, operand) in instr.operands.iter().enumerate() {
+            match &operand.value {
+                Value::VAR(name) => self.variable(name.split('.').next().unwrap(), operand.span),
+                Value::TYPE(typ) => self.typ(typ, operand.span),
+                Value::NAME(name) if !name.starts_with('@') => {
+                    match (&instr.instruction, signature.operands.get(i)) {
+                        (_, Some(Operand::FUNC)) => self.lookup(name, Kind::FUNCTION, operand.span),
+                        (Instruction::INST, Some(Operand::NAME)) => self.lookup(name, Kind::STRUCT, operand.span),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Name) {
+        let scope = self.scopes.last_mut().unwrap();
+
+        match scope.get(&name.name) {
+            Some(span) => {
+                let help = format!("it was first declared on line {}", span.line);
+                self.errors.push(name.span.error(format!("variable `{}` is already declared in this block", name.name)).with_help(help));
+            }
+            None => {
+                scope.insert(name.name.clone(), name.span);
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str, span: Span) {
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            return;
+        }
+
+        let visible: Vec<String> = self.scopes.iter().flat_map(|scope| scope.keys().cloned()).collect();
+
+        let error = span.error(format!("undefined variable `${name}`"));
+        self.errors.push(match suggest(name, &visible) {
+            Some(similar) => error.with_help(format!("did you mean `${similar}`?")),
+            None => error.with_help(format!("declare it with `VAR <type> {name}` before using it")),
+        });
+    }
+
+    fn typ(&mut self, typ: &Vec<Type>, span: Span) {
+        for t in typ {
+            // `STRUCT` on its own does not name one
+            match t {
+                Type::STRUCT(name) if name.len() > 0 => self.lookup(name, Kind::STRUCT, span),
+                _ => {}
+            }
+        }
+    }
+
+    // looks for `name` in the current module and then in the modules around it
+    fn lookup(&mut self, name: &str, kind: Kind, span: Span) {
+        let mut other = None;
+
+        for depth in (0..=self.module.len()).rev() {
+            let prefix = self.prefix(depth);
+            let path = prefix.clone() + name;
+
+            match self.symbols.get(&path) {
+                Some(symbol) if symbol.kind == kind => return,
+                Some(symbol) => other = other.or(Some(symbol.kind)),
+                None => {}
+            }
+
+            // nothing is known about the names in an included file without declarations
+            if self.opaque.iter().any(|opaque| path.starts_with(&(opaque.clone() + "."))) {
+                return;
+            }
+        }
+
+        if let Some(other) = other {
+            self.errors.push(span.error(format!("`{name}` is a {}, not a {}", other.as_str(), kind.as_str())));
+            return;
+        }
+
+        // the names that could have been meant, as they would be written here
+        let mut visible: Vec<String> = Vec::new();
+        for depth in 0..=self.module.len() {
+            let prefix = self.prefix(depth);
+
+            for (path, symbol) in &self.symbols {
+                if symbol.kind == kind && path.starts_with(&prefix) {
+                    visible.push(path[prefix.len()..].to_string());
+                }
+            }
+        }
+
+        let error = span.error(format!("undefined {} `{name}`", kind.as_str()));
+        self.errors.push(match suggest(name, &visible) {
+            Some(similar) => error.with_help(format!("did you mean `{similar}`?")),
+            None => error,
+        });
+    }
+
+    // `a.b.` for the first `depth` modules around the current item
+    fn prefix(&self, depth: usize) -> String {
+        return self.module[..depth].iter().map(|m| m.clone() + ".").collect();
+    }
+}
+
+// the variable `VAR` declares
+fn declared(instr: &Instr) -> Option<Name> {
+    if instr.instruction != Instruction::VAR {
+        return None;
+    }
+
+    match instr.operands.get(1) {
+        Some(operand) => match &operand.value {
+            Value::NAME(name) => Some(Name { name: name.clone(), span: operand.span }),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+// the closest of `candidates` to `name`, if it is close enough to be a typo
+fn suggest<'a>(name: &str, candidates: &'a Vec<String>) -> Option<&'a String> {
+    let max = (name.chars().count() / 3).max(1);
+
+    return candidates.iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(d, _)| *d <= max)
+        .min_by(|(a, x), (b, y)| a.cmp(b).then(x.cmp(y)))
+        .map(|(_, candidate)| candidate);
+}
+
+// the number of characters that have to be inserted, removed, replaced or swapped with the next one to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first `i` characters of `a` and the first `j` of `b`
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..=a.len() {
+        d[i][0] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    return d[a.len()][b.len()];
+}
@@ -1,7 +1,7 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, ops::{Index, Range}, str::FromStr};
 use lazy_static::lazy_static;
 
-use crate::number::Number;
+use crate::{diagnostic::Diagnostic, number::Number};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
@@ -71,6 +71,8 @@ lazy_static! {
     };
 }
 
+const OPERATOR_CHARS: &str = "=!<>&|";
+
 fn is_type(input: &str) -> bool {
     TYPE_MAP.contains_key(&input.to_uppercase()[..])
 }
@@ -92,166 +94,467 @@ pub enum Token {
     VAR(String),
 }
 
-macro_rules! push_type {
-    ($tokens:expr, $cur_token:expr, $temp_type:expr, $in_type:expr) => {
-        if is_type(&$cur_token) {
-            $temp_type.push(TYPE_MAP.get(&$cur_token.to_uppercase()[..]).unwrap().clone());
-            $cur_token = String::from("");
-            $in_type = true;
-        } else if $in_type {
-            $in_type = false;
-            $temp_type.reverse();
-            $tokens.push(Token::TYPE($temp_type.clone()));
-            $temp_type.clear();
+// a token along with where it came from in the original source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub line: usize,      // 1 based
+    pub col_start: usize, // 0 based, inclusive
+    pub col_end: usize,   // 0 based, exclusive
+    pub file_id: usize,   // index into the `SourceMap`
+}
+
+impl SpannedToken {
+    pub fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.file_id, self.line, self.col_start, self.col_end, message)
+    }
+}
+
+// a single tokenized source line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub number: usize,    // 1 based
+    pub file_id: usize,
+    pub col_start: usize, // first non whitespace column
+    pub col_end: usize,   // column after the last non whitespace, non comment character
+    pub tokens: Vec<SpannedToken>,
+}
+
+impl Line {
+    pub fn new(number: usize, file_id: usize, col_start: usize, col_end: usize, tokens: Vec<SpannedToken>) -> Line {
+        Line { number, file_id, col_start, col_end, tokens }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Token> {
+        self.tokens.get(index).map(|t| &t.token)
+    }
+
+    pub fn contains(&self, token: &Token) -> bool {
+        self.tokens.iter().any(|t| t.token == *token)
+    }
+
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.tokens.truncate(len);
+    }
+
+    // the tokens in `range` as a line of their own, spanning only those tokens
+    pub fn slice(&self, range: Range<usize>) -> Line {
+        let tokens = self.tokens[range].to_vec();
+
+        let (col_start, col_end) = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => (first.col_start, last.col_end),
+            _ => (self.col_start, self.col_end),
+        };
+
+        return Line::new(self.number, self.file_id, col_start, col_end, tokens);
+    }
+
+    // replaces `count` tokens starting at `index` with a single token spanning all of them
+    pub fn merge(&mut self, index: usize, count: usize, token: Token) {
+        let first = &self.tokens[index];
+        let last = &self.tokens[index + count - 1];
+
+        let merged = SpannedToken { token, line: first.line, col_start: first.col_start, col_end: last.col_end, file_id: first.file_id };
+
+        self.tokens.splice(index..index + count, [merged]);
+    }
+
+    pub fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.file_id, self.number, self.col_start, self.col_end, message)
+    }
+
+    // an error pointing at the token at `index`, or at the whole line if there is no such token
+    pub fn error_at(&self, index: usize, message: impl Into<String>) -> Diagnostic {
+        match self.tokens.get(index) {
+            Some(t) => t.error(message),
+            None => self.error(message),
         }
-    };
+    }
 }
 
-macro_rules! push {
-    ($tokens:expr, $cur_token:expr, $in_num:expr, $in_var:expr) => {
-        if $cur_token.len() > 0 {
-            if $in_num {
-                $tokens.push(Token::NUMBER(Number::from_str(&$cur_token).unwrap()));
-                $in_num = false;
-            } else if $in_var {
-                $tokens.push(Token::VAR($cur_token));
-                $in_var = false;
+impl Index<usize> for Line {
+    type Output = Token;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.tokens[index].token
+    }
+}
+
+struct Tokenizer {
+    line: usize,
+    file_id: usize,
+    tokens: Vec<SpannedToken>,
+
+    cur_token: String,
+    start: usize, // column the current token started at
+    in_num: bool,
+    in_var: bool,
+
+    in_type: bool,
+    temp_type: Vec<Type>,
+    type_start: usize,
+    type_end: usize,
+}
+
+impl Tokenizer {
+    fn push(&mut self, token: Token, col_start: usize, col_end: usize) {
+        self.tokens.push(SpannedToken { token, line: self.line, col_start, col_end, file_id: self.file_id });
+    }
+
+    // moves the current token into the pending type if it names one, otherwise emits the pending type
+    fn push_type(&mut self, col: usize) {
+        if is_type(&self.cur_token) {
+            if !self.in_type {
+                self.type_start = self.start;
+            }
+            self.type_end = col;
+
+            self.temp_type.push(TYPE_MAP.get(&self.cur_token.to_uppercase()[..]).unwrap().clone());
+            self.cur_token = String::from("");
+            self.in_type = true;
+        } else {
+            self.end_type();
+        }
+    }
+
+    // emits the pending type, if there is one
+    fn end_type(&mut self) {
+        if self.in_type {
+            self.in_type = false;
+            self.temp_type.reverse();
+            self.push(Token::TYPE(self.temp_type.clone()), self.type_start, self.type_end);
+            self.temp_type.clear();
+        }
+    }
+
+    // emits the current token as a number, variable or identifier
+    fn push_word(&mut self, col: usize) -> Result<(), Diagnostic> {
+        if self.cur_token.len() > 0 {
+            let cur_token = std::mem::take(&mut self.cur_token);
+
+            if self.in_num {
+                let number = self.parse_number(&cur_token, col)?;
+                self.push(Token::NUMBER(number), self.start, col);
+                self.in_num = false;
+            } else if self.in_var {
+                self.push(Token::VAR(cur_token), self.start, col);
+                self.in_var = false;
             } else {
-                $tokens.push(Token::IDENT($cur_token));
+                self.push(Token::IDENT(cur_token), self.start, col);
             }
+        }
+
+        return Ok(());
+    }
+
+    // emits anything pending followed by a single character token
+    fn push_token(&mut self, token: Token, col: usize) -> Result<(), Diagnostic> {
+        // a single character token ends a type, like the `)` in `(i32)`
+        self.push_type(col);
+        self.end_type();
+        self.push_word(col)?;
+        self.push(token, col, col + 1);
 
-            $cur_token = String::from("");
+        return Ok(());
+    }
+
+    // operators like `==` and `&&` are words of their own, even without spaces around them
+    fn split_operator(&mut self, c: char, col: usize) -> Result<(), Diagnostic> {
+        let is_operator = |s: &String| s.chars().all(|c| OPERATOR_CHARS.contains(c));
+
+        if self.cur_token.len() > 0 && is_operator(&self.cur_token) != OPERATOR_CHARS.contains(c) {
+            self.push_type(col);
+            self.push_word(col)?;
+
+            self.start = col;
         }
-    };
-}
 
-macro_rules! push_token {
-    ($token:expr, $tokens:expr, $cur_token:expr, $temp_type:expr, $in_type:expr, $in_num:expr, $in_var:expr) => {
-        push_type!($tokens, $cur_token, $temp_type, $in_type);
-        push!($tokens, $cur_token, $in_num, $in_var);
-        $tokens.push($token);
+        return Ok(());
+    }
+
+    fn parse_number(&self, s: &String, col: usize) -> Result<Number, Diagnostic> {
+        return Number::from_str(s).map_err(|e| {
+            Diagnostic::new(self.file_id, self.line, self.start, col, format!("{e}: `{s}`"))
+        });
     }
 }
 
-// TODO: character literals
-pub fn tokenize(line: String) -> Vec<Token> {
-    let mut tokens: Vec<Token> = Vec::new();
+// decodes the escape sequences `\n \t \r \0 \\ \' \" \xNN \u{...}`
+// strings only allow ascii `\xNN` escapes since they have to stay valid utf-8
+fn unescape(s: &str, in_string: bool) -> Result<String, String> {
+    let mut res = String::new();
+    let mut chars = s.chars();
 
-    let mut cur_token: String = String::from("");
-    let mut in_str = false;
-    let mut in_num = false;
-    let mut in_var = false;
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
 
-    let mut in_type = false;
-    let mut temp_type: Vec<Type> = Vec::new();
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some('0') => res.push('\0'),
+            Some('\\') => res.push('\\'),
+            Some('\'') => res.push('\''),
+            Some('"') => res.push('"'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
 
-    for c in line.chars() {
-        if !in_str {
-            match c {
-                '-' | '0'..='9' => {
-                    if cur_token.len() == 0 {
-                        in_num = true;
-                    }
-                    cur_token.push(c);
+                // `from_str_radix` also takes a sign, like the `+` in `\x+1`
+                if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("invalid escape sequence `\\x{hex}`, expected two hex digits"));
                 }
-                '(' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::LPAREN, tokens, cur_token, temp_type, in_type, in_num, in_var);
+
+                let b = u8::from_str_radix(&hex, 16).unwrap();
+                if in_string && b > 0x7F {
+                    return Err(format!("out of range escape `\\x{hex}`, strings only allow up to `\\x7F`"));
                 }
-                ')' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::RPAREN, tokens, cur_token, temp_type, in_type, in_num, in_var);
+
+                res.push(b as char);
+            }
+            Some('u') => {
+                if chars.next() != Some('{') {
+                    return Err(String::from("expected `{` after `\\u`"));
                 }
-                '{' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::LCURLY, tokens, cur_token, temp_type, in_type, in_num, in_var);
+
+                let mut hex = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => hex.push(c),
+                        None => return Err(format!("unterminated unicode escape `\\u{{{hex}`, expected `}}`")),
+                    }
                 }
-                '}' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::RCURLY, tokens, cur_token, temp_type, in_type, in_num, in_var);
+
+                let digits = hex.chars().all(|c| c.is_ascii_hexdigit());
+                match u32::from_str_radix(&hex, 16).ok().filter(|_| digits).and_then(char::from_u32) {
+                    Some(c) => res.push(c),
+                    None => return Err(format!("invalid unicode escape `\\u{{{hex}}}`")),
                 }
-                '[' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::LSQUARE, tokens, cur_token, temp_type, in_type, in_num, in_var);
+            }
+            Some(c) => return Err(format!("unknown escape sequence `\\{c}`")),
+            None => return Err(String::from("unterminated escape sequence")),
+        }
+    }
+
+    return Ok(res);
+}
+
+pub fn tokenize(line: String, number: usize, file_id: usize) -> Result<Line, Diagnostic> {
+    let mut t = Tokenizer {
+        line: number,
+        file_id,
+        tokens: Vec::new(),
+
+        cur_token: String::from(""),
+        start: 0,
+        in_num: false,
+        in_var: false,
+
+        in_type: false,
+        temp_type: Vec::new(),
+        type_start: 0,
+        type_end: 0,
+    };
+
+    let mut in_str = false;
+    let mut in_char = false;
+    let mut escaped = false;
+
+    // raw strings look like r"..." or r#"..."#, with any number of #
+    let mut in_raw = false;
+    let mut hashes = 0;
+    let mut skip = 0;
+
+    let mut col_start = None;
+    let mut col_end = 0;
+
+    let chars: Vec<char> = line.chars().collect();
+    let len = chars.len();
+
+    for (col, &c) in chars.iter().enumerate() {
+
+        if !in_str && !in_char && !t.in_var && t.cur_token.len() == 0 {
+            t.start = col;
+        }
+
+        if in_str || in_char || !(c.is_whitespace() || c == ';') {
+            col_start.get_or_insert(col);
+            col_end = col + 1;
+        }
+
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+
+        if in_char {
+            match c {
+                '\'' if !escaped => {
+                    let raw = std::mem::take(&mut t.cur_token);
+                    let value = unescape(&raw, false).map_err(|e| Diagnostic::new(file_id, number, t.start, col + 1, e))?;
+
+                    let mut chars = value.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => t.push(Token::NUMBER(Number::UNSIGNED(c as u64)), t.start, col + 1),
+                        (None, _) => return Err(Diagnostic::new(file_id, number, t.start, col + 1, "empty character literal")),
+                        _ => return Err(Diagnostic::new(file_id, number, t.start, col + 1, "character literal may only contain one character")
+                            .with_help("use double quotes for strings")),
+                    }
+
+                    in_char = false;
                 }
-                ']' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::RSQUARE, tokens, cur_token, temp_type, in_type, in_num, in_var);
+                _ => {
+                    escaped = !escaped && c == '\\';
+                    t.cur_token.push(c);
                 }
-                ':' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push_token!(Token::COLON, tokens, cur_token, temp_type, in_type, in_num, in_var);
+            }
+        } else if !in_str {
+            match c {
+                '-' | '0'..='9' => {
+                    t.split_operator(c, col)?;
+                    if t.cur_token.len() == 0 {
+                        t.in_num = true;
+                    }
+                    t.cur_token.push(c);
                 }
+                '(' => t.push_token(Token::LPAREN, col)?,
+                ')' => t.push_token(Token::RPAREN, col)?,
+                '{' => t.push_token(Token::LCURLY, col)?,
+                '}' => t.push_token(Token::RCURLY, col)?,
+                '[' => t.push_token(Token::LSQUARE, col)?,
+                ']' => t.push_token(Token::RSQUARE, col)?,
+                ':' => t.push_token(Token::COLON, col)?,
                 '"' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push!(tokens, cur_token, in_num, in_var);
+                    let cur_token = &t.cur_token;
+                    in_raw = !t.in_num && !t.in_var && cur_token.starts_with('r') && cur_token[1..].chars().all(|c| c == '#');
+
+                    if in_raw {
+                        // a type before the string comes first, `r` itself never names one
+                        t.push_type(col);
+
+                        // the string starts at the `r`
+                        hashes = t.cur_token.len() - 1;
+                        t.cur_token.clear();
+                    } else {
+                        t.push_type(col);
+                        t.push_word(col)?;
+
+                        t.start = col;
+                    }
 
                     in_str = true;
                 }
+                '\'' => {
+                    t.push_type(col);
+                    t.push_word(col)?;
+
+                    t.start = col;
+                    in_char = true;
+                }
                 '.' => {
-                    if in_num {
-                        cur_token.push(c);
+                    if t.in_num {
+                        t.cur_token.push(c);
                     } else {
-                        push_type!(tokens, cur_token, temp_type, in_type);
-                        push!(tokens, cur_token, in_num, in_var);
-                        tokens.push(Token::DOT);
+                        t.push_type(col);
+                        t.push_word(col)?;
+                        t.push(Token::DOT, col, col + 1);
                     }
                 }
                 '*' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    cur_token.push(c);
-                }
-                '_' => {
-                    if !in_num {
-                        cur_token.push(c);
+                    t.push_type(col);
+                    if t.cur_token.len() == 0 {
+                        t.start = col;
                     }
+                    t.cur_token.push(c);
                 }
                 ' ' | '\r' | '\n' | '\t' | ',' => {
-                    push_type!(tokens, cur_token, temp_type, in_type);
-                    push!(tokens, cur_token, in_num, in_var);
+                    t.push_type(col);
+                    t.push_word(col)?;
                 }
                 ';' => {
                     break;
                 }
                 '$' => {
-                    in_var = true;
+                    t.split_operator(c, col)?;
+                    t.in_var = true;
+                }
+                _ => {
+                    t.split_operator(c, col)?;
+                    t.cur_token.push(c);
                 }
-                _ => cur_token.push(c)
             }
         } else {
             // yes im using match for this
             match c {
-                '"' => {
-                    tokens.push(Token::STRING(cur_token));
+                '"' if in_raw => {
+                    let end = col + 1 + hashes;
+
+                    if end <= len && chars[col + 1..end].iter().all(|c| *c == '#') {
+                        let s = std::mem::take(&mut t.cur_token);
+                        t.push(Token::STRING(s), t.start, end);
 
-                    cur_token = String::from("");
+                        skip = hashes;
+                        in_str = false;
+                        in_raw = false;
+                    } else {
+                        t.cur_token.push(c);
+                    }
+                }
+                '"' if !escaped => {
+                    let raw = std::mem::take(&mut t.cur_token);
+                    let s = unescape(&raw, true).map_err(|e| Diagnostic::new(file_id, number, t.start, col + 1, e))?;
+                    t.push(Token::STRING(s), t.start, col + 1);
 
                     in_str = false;
                 }
-                _ => cur_token.push(c)
+                _ => {
+                    escaped = !in_raw && !escaped && c == '\\';
+                    t.cur_token.push(c);
+                }
             }
         }
     }
 
-    // inlined to remove warnings
-    if is_type(&cur_token){
-        temp_type.push(TYPE_MAP.get(&cur_token.to_uppercase()[..]).unwrap().clone());
-        cur_token = String::from("");
-    } else if in_type {
-        temp_type.reverse();
-        tokens.push(Token::TYPE(temp_type.clone()));
-        temp_type.clear();
+    if in_char {
+        return Err(Diagnostic::new(file_id, number, t.start, len, "unterminated character literal"));
     }
 
-    if cur_token.len() > 0 {
-        if in_num {
-            tokens.push(Token::NUMBER(Number::from_str(&cur_token).unwrap()));
-        } else if in_var {
-            tokens.push(Token::VAR(cur_token));
-        } else {
-            tokens.push(Token::IDENT(cur_token));
+    if in_str {
+        return Err(Diagnostic::new(file_id, number, t.start, len, "unterminated string"));
+    }
+
+    t.push_type(len);
+    t.end_type();
+    t.push_word(len)?;
+
+    return Ok(Line::new(number, file_id, col_start.unwrap_or(0), col_end, t.tokens));
+}
+
+// tokenizes a whole file, lines that fail to tokenize are kept empty so line numbers stay intact
+pub fn tokenize_file(contents: &str, file_id: usize) -> (Vec<Line>, Vec<Diagnostic>) {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut errors: Vec<Diagnostic> = Vec::new();
+
+    for (i, line) in contents.split("\n").enumerate() {
+        match tokenize(line.to_string(), i + 1, file_id) {
+            Ok(line) => lines.push(line),
+            Err(e) => {
+                errors.push(e);
+                lines.push(Line::new(i + 1, file_id, 0, 0, Vec::new()));
+            }
         }
     }
 
-    return tokens;
+    return (lines, errors);
 }
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::{ast::{Field, Instr, Item, Operand, Program, Stmt, Type, Value}, data::encode, diagnostic::Diagnostic, instruction::Instruction, number::{suffix_name, Number}};
+
+// what is known about the type of an operand
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_camel_case_types)]
+enum Kind {
+    INTEGER(Number), // a literal without a suffix, it can be any number type it fits in
+    DECIMAL,         // a literal with a fraction and without a suffix, it can be any float type
+    OF(Vec<Type>),
+}
+
+// warns about operands whose types do not fit together, using the types of `VAR`s, arguments and struct fields
+// only runs once every name is known to be defined, operands of unknown type are not checked
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = Checker { structs: HashMap::new(), constants: HashMap::new(), scopes: Vec::new(), module: Vec::new(), function: None, warnings: Vec::new() };
+
+    checker.define_structs(&program.items, "");
+    checker.define_constants(&program.items);
+    checker.items(&program.items);
+
+    return checker.warnings;
+}
+
+struct Checker {
+    structs: HashMap<String, Vec<Field>>, // by path, like `m.S`
+    constants: HashMap<String, Vec<Type>>, // the `.data` section, the same in every module
+    scopes: Vec<HashMap<String, Vec<Type>>>, // the variables of the blocks around the current statement, innermost last
+    module: Vec<String>,                  // the modules around the current item
+    function: Option<(String, Vec<Type>)>, // the name and return type of the current function
+    warnings: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn define_structs(&mut self, items: &Vec<Item>, prefix: &str) {
+        for item in items {
+            match item {
+                Item::STRUCT(s) => {
+                    self.structs.insert(format!("{prefix}{}", s.name.name), s.fields.clone());
+                }
+                Item::MODULE(module) => self.define_structs(&module.items, &format!("{prefix}{}.", module.name.name)),
+                _ => {}
+            }
+        }
+    }
+
+    fn define_constants(&mut self, items: &Vec<Item>) {
+        for item in items {
+            match item {
+                Item::DATA(constants) => {
+                    for constant in constants {
+                        self.constants.insert(constant.name.name.clone(), constant.typ.clone());
+                    }
+                }
+                Item::MODULE(module) => self.define_constants(&module.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn items(&mut self, items: &Vec<Item>) {
+        // variables declared outside of functions can be used by every function around them
+        let mut scope = HashMap::new();
+        for item in items {
+            if let Item::STMT(Stmt::INSTR(instr)) = item {
+                if let Some((name, typ)) = declared(instr) {
+                    scope.insert(name, typ);
+                }
+            }
+        }
+        self.scopes.push(scope);
+
+        for item in items {
+            match item {
+                Item::FUNCTION(function) => {
+                    let mut scope = HashMap::new();
+                    for arg in &function.args {
+                        scope.insert(arg.name.name.clone(), arg.typ.clone());
+                    }
+
+                    self.function = Some((function.name.name.clone(), function.ret_type.clone()));
+                    self.scopes.push(scope);
+                    self.stmts(&function.body);
+                    self.scopes.pop();
+                    self.function = None;
+                }
+                Item::MODULE(module) => {
+                    self.module.push(module.name.name.clone());
+                    self.items(&module.items);
+                    self.module.pop();
+                }
+                Item::STMT(stmt) => self.stmt(stmt),
+                _ => {}
+            }
+        }
+
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &Vec<Stmt>) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::INSTR(instr) => {
+                self.instr(instr);
+
+                if let Some((name, typ)) = declared(instr) {
+                    self.scopes.last_mut().unwrap().insert(name, typ);
+                }
+            }
+            Stmt::LABEL(_) => {}
+            Stmt::SCOPE(body) => self.block(body),
+            // branches don't open a scope of their own, what they declare is seen after `.end`
+            Stmt::IF(chain) => {
+                for branch in &chain.branches {
+                    self.stmts(&branch.body);
+                }
+
+                if let Some(body) = &chain.otherwise {
+                    self.stmts(body);
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, stmts: &Vec<Stmt>) {
+        self.scopes.push(HashMap::new());
+        self.stmts(stmts);
+        self.scopes.pop();
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        let ops = &instr.operands;
+        let name = instr.name.name.to_uppercase();
+
+        match instr.instruction {
+            Instruction::ADD | Instruction::SUB | Instruction::MUL | Instruction::DIV | Instruction::MOD => self.same(&name, ops),
+            Instruction::AND | Instruction::OR | Instruction::XOR | Instruction::NOT => {
+                if self.integers(&name, ops) {
+                    self.same(&name, ops);
+                }
+            }
+            // the shift amount can be any integer
+            Instruction::LSH | Instruction::RSH => {
+                if self.integers(&name, ops) && ops.len() == 3 {
+                    self.same(&name, &vec![ops[0].clone(), ops[2].clone()]);
+                }
+            }
+            Instruction::MOV => {
+                if ops.len() == 2 {
+                    self.moves(&ops[0], &ops[1]);
+                }
+            }
+            Instruction::DEREF => {
+                if let Some(pointee) = self.pointer(&name, ops.first()) {
+                    if let Some(dest) = ops.get(1) {
+                        self.stores(&pointee, dest, &format!("the pointer `{}` points to", show_operand(&ops[0])));
+                    }
+                }
+            }
+            Instruction::PMOV => {
+                if let Some(pointee) = self.pointer(&name, ops.get(1)) {
+                    self.moves_into(&ops[0], &pointee, &format!("the pointer `{}` points to", show_operand(&ops[1])));
+                }
+            }
+            Instruction::REF => {
+                self.pointer(&name, ops.get(1));
+            }
+            Instruction::ALLOC => {
+                self.pointer(&name, ops.get(2));
+            }
+            Instruction::FREE => {
+                self.pointer(&name, ops.first());
+            }
+            Instruction::RET => self.ret(instr),
+            _ => {}
+        }
+    }
+
+    // the operands of arithmetic all have to be of the same type
+    fn same(&mut self, name: &str, ops: &Vec<Operand>) {
+        let mut first: Option<(&Operand, Vec<Type>)> = None;
+
+        for op in ops {
+            if let Some(Kind::OF(typ)) = self.kind(op) {
+                match &first {
+                    None => first = Some((op, typ)),
+                    Some((other, expected)) if !fits(&typ, expected) => {
+                        self.warnings.push(op.span.warning(format!("mismatched types in `{name}`, `{}` is a `{}` but `{}` is a `{}`", show_operand(other), show(expected), show_operand(op), show(&typ)))
+                            .with_help(format!("`{name}` works on values of one type")));
+                        return;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        // literals have to fit the type of the variables
+        if let Some((other, expected)) = first {
+            for op in ops {
+                if !matches!(self.kind(op), Some(Kind::OF(_))) {
+                    self.literal(op, &expected, &format!("`{}`", show_operand(other)));
+                }
+            }
+        }
+    }
+
+    // whether none of the operands are floats
+    fn integers(&mut self, name: &str, ops: &Vec<Operand>) -> bool {
+        for op in ops {
+            let float = match self.kind(op) {
+                Some(Kind::DECIMAL) => true,
+                Some(Kind::OF(typ)) => is_float(&typ),
+                _ => false,
+            };
+
+            if float {
+                self.warnings.push(op.span.warning(format!("`{name}` works on integers, but `{}` is a float", show_operand(op))));
+                return false;
+            }
+        }
+
+        return true;
+    }
+
+    // `MOV (src) (dest)`
+    fn moves(&mut self, src: &Operand, dest: &Operand) {
+        if let Some(Kind::OF(typ)) = self.kind(dest) {
+            self.moves_into(src, &typ, &format!("`{}`", show_operand(dest)));
+        }
+    }
+
+    // a value stored into something of type `dest`, `what` says what that is
+    fn moves_into(&mut self, src: &Operand, dest: &Vec<Type>, what: &str) {
+        match self.kind(src) {
+            Some(Kind::OF(typ)) if is_float(&typ) && is_integer(dest) => {
+                self.warnings.push(src.span.warning(format!("moving the `{}` `{}` into {what}, which is a `{}`", show(&typ), show_operand(src), show(dest)))
+                    .with_help("the fraction is lost"));
+            }
+            Some(Kind::OF(typ)) if !fits(&typ, dest) => {
+                self.warnings.push(src.span.warning(format!("`{}` is a `{}`, but {what} is a `{}`", show_operand(src), show(&typ), show(dest))));
+            }
+            Some(Kind::OF(_)) | None => {}
+            Some(_) => self.literal(src, dest, what),
+        }
+    }
+
+    // `DEREF (pointer) (dest)`, the value the pointer points to is stored into `dest`
+    fn stores(&mut self, pointee: &Vec<Type>, dest: &Operand, what: &str) {
+        if let Some(Kind::OF(typ)) = self.kind(dest) {
+            if !fits(pointee, &typ) {
+                self.warnings.push(dest.span.warning(format!("{what} is a `{}`, but `{}` is a `{}`", show(pointee), show_operand(dest), show(&typ))));
+            }
+        }
+    }
+
+    // a literal used where a value of type `typ` is expected
+    fn literal(&mut self, op: &Operand, typ: &Vec<Type>, what: &str) {
+        match self.kind(op) {
+            Some(Kind::DECIMAL) if is_integer(typ) => {
+                self.warnings.push(op.span.warning(format!("moving the decimal `{}` into {what}, which is a `{}`", show_operand(op), show(typ)))
+                    .with_help("the fraction is lost"));
+            }
+            Some(Kind::INTEGER(n)) if typ.len() == 1 && (is_integer(typ) || is_float(typ)) => {
+                if encode(&n, &typ[0]).is_err() {
+                    self.warnings.push(op.span.warning(format!("`{n}` does not fit in {what}, which is a `{}`", show(typ))));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // an operand that has to be a pointer, returns the type it points to if it is one
+    fn pointer(&mut self, name: &str, op: Option<&Operand>) -> Option<Vec<Type>> {
+        let op = op?;
+
+        match self.kind(op)? {
+            Kind::OF(typ) if typ.first() == Some(&Type::POINTER) => return Some(typ[1..].to_vec()),
+            Kind::OF(typ) => {
+                self.warnings.push(op.span.warning(format!("`{name}` needs a pointer, but `{}` is a `{}`", show_operand(op), show(&typ))));
+            }
+            Kind::DECIMAL => {
+                self.warnings.push(op.span.warning(format!("`{name}` needs a pointer, but `{}` is a decimal", show_operand(op))));
+            }
+            // an address
+            Kind::INTEGER(_) => {}
+        }
+
+        return None;
+    }
+
+    fn ret(&mut self, instr: &Instr) {
+        let (function, ret_type) = match &self.function {
+            Some(f) => f.clone(),
+            None => return,
+        };
+
+        let void = ret_type.len() == 0 || ret_type == vec![Type::VOID];
+
+        match instr.operands.first() {
+            Some(op) if void => {
+                self.warnings.push(op.span.warning(format!("`{function}` returns `void`, but `RET` has a value")));
+            }
+            Some(op) => self.moves_into(op, &ret_type, &format!("the return value of `{function}`")),
+            None if !void => {
+                self.warnings.push(instr.name.span.warning(format!("`{function}` returns `{}`, but `RET` has no value", show(&ret_type))));
+            }
+            None => {}
+        }
+    }
+
+    fn kind(&self, op: &Operand) -> Option<Kind> {
+        match &op.value {
+            Value::NUMBER(Number::TYPED(typ, _)) => Some(Kind::OF(vec![typ.clone()])),
+            Value::NUMBER(Number::DECIMAL(_)) => Some(Kind::DECIMAL),
+            Value::NUMBER(n) => Some(Kind::INTEGER(n.clone())),
+            Value::VAR(name) => self.variable(name).map(Kind::OF),
+            // constants from the `.data` section, a variable with the same name is written with `$`
+            Value::NAME(name) => self.constants.get(name).cloned().map(Kind::OF),
+            _ => None,
+        }
+    }
+
+    // `name` or `name.field.field`
+    fn variable(&self, name: &str) -> Option<Vec<Type>> {
+        let mut parts = name.split('.');
+        let mut typ = self.lookup_var(parts.next().unwrap())?;
+
+        for field in parts {
+            typ = match typ.as_slice() {
+                [Type::STRUCT(s)] => self.lookup_struct(s)?.iter().find(|f| f.name.name == field)?.typ.clone(),
+                _ => return None,
+            };
+        }
+
+        return Some(typ);
+    }
+
+    fn lookup_var(&self, name: &str) -> Option<Vec<Type>> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned();
+    }
+
+    // looks in the current module and then in the modules around it
+    fn lookup_struct(&self, name: &str) -> Option<&Vec<Field>> {
+        for depth in (0..=self.module.len()).rev() {
+            let prefix: String = self.module[..depth].iter().map(|m| m.clone() + ".").collect();
+
+            if let Some(fields) = self.structs.get(&(prefix + name)) {
+                return Some(fields);
+            }
+        }
+
+        return None;
+    }
+}
+
+// the variable `VAR` declares and its type, if it is written out
+fn declared(instr: &Instr) -> Option<(String, Vec<Type>)> {
+    if instr.instruction != Instruction::VAR || instr.operands.len() != 2 {
+        return None;
+    }
+
+    match (&instr.operands[0].value, &instr.operands[1].value) {
+        (Value::TYPE(typ), Value::NAME(name)) => Some((name.clone(), typ.clone())),
+        _ => None,
+    }
+}
+
+fn is_integer(typ: &Vec<Type>) -> bool {
+    return matches!(typ.as_slice(), [Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::U8 | Type::U16 | Type::U32 | Type::U64]);
+}
+
+fn is_float(typ: &Vec<Type>) -> bool {
+    return matches!(typ.as_slice(), [Type::F16 | Type::F32 | Type::F64]);
+}
+
+// a plain `*` fits any pointer
+fn fits(a: &Vec<Type>, b: &Vec<Type>) -> bool {
+    if a == b {
+        return true;
+    }
+
+    return (a == &vec![Type::POINTER] && b.first() == Some(&Type::POINTER)) || (b == &vec![Type::POINTER] && a.first() == Some(&Type::POINTER));
+}
+
+// a type the way it is written, like `i32*`
+fn show(typ: &Vec<Type>) -> String {
+    let mut res = String::new();
+
+    for t in typ.iter().rev() {
+        res += &match t {
+            Type::POINTER => String::from("*"),
+            Type::VOID => String::from("void"),
+            Type::TYPE => String::from("type"),
+            Type::NAME => String::from("name"),
+            Type::STRUCT(name) if name.len() > 0 => format!("struct {name}"),
+            Type::STRUCT(_) => String::from("struct"),
+            t => suffix_name(t).to_string(),
+        };
+    }
+
+    return res;
+}
+
+// an operand the way it is written
+fn show_operand(op: &Operand) -> String {
+    match &op.value {
+        Value::NUMBER(Number::DECIMAL(n)) => format!("{n:?}"),
+        Value::NUMBER(n) => n.to_string(),
+        Value::VAR(name) => format!("${name}"),
+        Value::STRING(s) => format!("{s:?}"),
+        Value::NAME(name) => name.clone(),
+        Value::LABEL(name) => format!(":{name}"),
+        Value::TYPE(typ) => show(typ),
+    }
+}
@@ -0,0 +1,39 @@
+// helpers shared by the tests, not every test file uses all of them
+#![allow(dead_code)]
+
+use rasm::{Assembler, Diagnostic, MemoryFileSystem};
+
+pub fn assemble(src: &str) -> Vec<u8> {
+    match Assembler::new().file_system(MemoryFileSystem::new()).assemble_str(src) {
+        Ok(bytes) => bytes,
+        Err(diagnostics) => panic!("failed to assemble `{src}`:\n{diagnostics}"),
+    }
+}
+
+// the message of the first error
+pub fn assemble_err(src: &str) -> String {
+    return diagnostics(MemoryFileSystem::new(), src)[0].message.clone();
+}
+
+pub fn diagnostics(fs: MemoryFileSystem, src: &str) -> Vec<Diagnostic> {
+    match Assembler::new().file_system(fs).assemble_str(src) {
+        Ok(_) => panic!("`{src}` assembled, but should not have"),
+        Err(diagnostics) => diagnostics.errors,
+    }
+}
+
+pub fn assemble_file_err(fs: MemoryFileSystem, path: &str) -> Vec<String> {
+    match Assembler::new().file_system(fs).assemble_file(path) {
+        Ok(_) => panic!("`{path}` assembled, but should not have"),
+        Err(diagnostics) => diagnostics.errors.iter().map(|e| e.message.clone()).collect(),
+    }
+}
+
+pub fn warnings(src: &str) -> Vec<String> {
+    let mut asm = Assembler::new().file_system(MemoryFileSystem::new());
+
+    match asm.assemble_str(src) {
+        Ok(_) => asm.warnings().warnings.iter().map(|w| w.message.clone()).collect(),
+        Err(diagnostics) => panic!("failed to assemble `{src}`:\n{diagnostics}"),
+    }
+}
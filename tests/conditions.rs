@@ -0,0 +1,60 @@
+// `.if` chains are decided while assembling when every name is defined, and left for the runtime otherwise
+use common::*;
+use rasm::Assembler;
+
+mod common;
+
+// the branch `condition` picks, `NOP` when it is true and `RET` when it is false
+fn branch(condition: &str) -> Vec<u8> {
+    return assemble(&format!(".define A 2\n.if {condition}\nNOP\n.else\nRET\n.end"));
+}
+
+#[test]
+fn the_example_assembles() {
+    if let Err(diagnostics) = Assembler::new().assemble_file("examples/conditional_parsing.rasm") {
+        panic!("failed to assemble the example:\n{diagnostics}");
+    }
+}
+
+#[test]
+fn names_compared_with_runtime_names_are_left_for_the_runtime() {
+    let runtime = assemble(".runtime PLATFORM\n.if PLATFORM == PLATFORM_LINUX\nNOP\n.end");
+
+    assert_eq!(runtime, assemble(".runtime PLATFORM\n.runtime PLATFORM_LINUX\n.if PLATFORM == PLATFORM_LINUX\nNOP\n.end"));
+    assert_ne!(runtime, assemble(""));
+}
+
+#[test]
+fn elseif_conditions_of_runtime_chains_use_defines() {
+    let src = ".runtime PLATFORM\n.define LINUX 3\n.define DEBUG 1\n.if PLATFORM == 2\nNOP\n.elseif PLATFORM == LINUX\nRET\n.elseif DEBUG\nNOP\n.end";
+    let expanded = ".runtime PLATFORM\n.if PLATFORM == 2\nNOP\n.elseif PLATFORM == 3\nRET\n.elseif 1 != 0\nNOP\n.end";
+
+    assert_eq!(assemble(src), assemble(expanded));
+}
+
+#[test]
+fn conditions_are_folded() {
+    let (yes, no) = (assemble("NOP"), assemble("RET"));
+
+    assert_eq!(branch("A > 1 && !defined(B)"), yes);
+    assert_eq!(branch("0 || 1 && 0"), no);
+    assert_eq!(branch("!(0 || 1)"), no);
+    assert_eq!(branch("(0 || 1) && A == 2"), yes);
+    assert_eq!(branch("\"a\" < \"b\""), yes);
+    assert_eq!(branch("A != 2 || \"\""), no);
+}
+
+#[test]
+fn and_and_or_short_circuit() {
+    let (yes, no) = (assemble("NOP"), assemble("RET"));
+
+    assert_eq!(branch("defined(LEVEL) && LEVEL > 2"), no);
+    assert_eq!(branch("!defined(LEVEL) || LEVEL > 2"), yes);
+    assert_eq!(assemble(".define LINUX 2\n.runtime PLATFORM\n.if defined(DEBUG) && PLATFORM == LINUX\nNOP\n.end"), assemble(""));
+}
+
+#[test]
+fn condition_errors() {
+    assert_eq!(assemble_err(".if X\nNOP\n.end"), "`X` is not defined");
+    assert_eq!(assemble_err(".if 1 == \"a\"\nNOP\n.end"), "cannot compare a number with a string");
+}
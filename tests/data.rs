@@ -0,0 +1,26 @@
+// constants from the `.data` section, checked against their type and referenced by name
+use rainbow_wrapper::{*, types::Type, wrapper::Wrapper};
+use common::*;
+
+mod common;
+
+#[test]
+fn numbers_are_pushed_into_the_data_section() {
+    let mut wrapper = Wrapper::new();
+    wrapper.push_data(&"len_1".to_string(), vec![Type::U8], vec![13]);
+    wrapper.push(push!(ident!("len_1")));
+
+    assert_eq!(assemble("PUSH len_1\n.data\nlen_1 u8 13"), wrapper.emit());
+}
+
+#[test]
+fn strings_become_the_string_itself() {
+    assert_eq!(assemble("PUSH str_1\n.data\nstr_1 char* \"Hello, World!\""), assemble("PUSH \"Hello, World!\""));
+}
+
+#[test]
+fn values_are_checked_against_their_type() {
+    assert_eq!(assemble_err(".data\nlen_1 u8 300"), "300 does not fit in a u8");
+    assert_eq!(assemble_err(".data\nbig f32 1e300"), "1e300 does not fit in a f32");
+    assert_eq!(assemble_err(".data\nbig f16 70000"), "7e4 does not fit in a f16");
+}
@@ -0,0 +1,12 @@
+// the make rules written with `-M` and into the `.d` files
+use rasm::{Assembler, MemoryFileSystem};
+
+#[test]
+fn paths_are_escaped_for_make() {
+    let fs = MemoryFileSystem::new().with_file("my $dir/a b#1.rasm", "NOP");
+
+    let mut asm = Assembler::new().file_system(fs);
+    asm.assemble_file("my $dir/a b#1.rasm").unwrap();
+
+    assert_eq!(asm.make_rule("$out.rbb"), "$$out.rbb: my\\ $$dir/a\\ b\\#1.rasm\n");
+}
@@ -0,0 +1,50 @@
+// included files are found the same way whether their paths are relative, absolute, at the root or go through `..`
+use common::*;
+use rasm::{FileSystem, MemoryFileSystem};
+
+mod common;
+
+// `io.println` is defined and `io.nope` is not, so the only error shows the declarations of `io` were found
+const MAIN: &str = "void main() { CALL io.println CALL io.nope }";
+const IO: &str = "void println() { }";
+
+#[test]
+fn absolute_includes() {
+    let fs = MemoryFileSystem::new().with_file("/lib/io.rasm", IO).with_file("src/main.rasm", format!(".include \"/lib/io.rasm\"\n{MAIN}"));
+
+    assert_eq!(assemble_file_err(fs, "src/main.rasm"), vec!["undefined function `io.nope`"]);
+}
+
+#[test]
+fn includes_going_up_a_folder() {
+    let fs = MemoryFileSystem::new().with_file("lib/io.rasm", IO).with_file("src/main.rasm", format!(".include \"../lib/io.rasm\"\n{MAIN}"));
+
+    assert_eq!(assemble_file_err(fs, "src/main.rasm"), vec!["undefined function `io.nope`"]);
+}
+
+#[test]
+fn includes_from_a_file_at_the_root() {
+    let fs = MemoryFileSystem::new().with_file("/io.rasm", IO).with_file("/main.rasm", format!(".include \"io.rasm\"\n{MAIN}"));
+    assert_eq!(assemble_file_err(fs, "/main.rasm"), vec!["undefined function `io.nope`"]);
+
+    // found in a subfolder of the root
+    let fs = MemoryFileSystem::new().with_file("/std/io.rasm", IO).with_file("/main.rasm", format!(".include \"io.rasm\"\n{MAIN}"));
+    assert_eq!(assemble_file_err(fs, "/main.rasm"), vec!["undefined function `io.nope`"]);
+}
+
+#[test]
+fn the_same_file_through_dot_dot_is_a_cycle() {
+    let fs = MemoryFileSystem::new().with_file("src/a.rasm", ".include \"../src/a.rasm\"");
+
+    assert_eq!(assemble_file_err(fs, "src/a.rasm")[0], "`../src/a.rasm` includes itself");
+}
+
+#[test]
+fn the_working_directory_has_the_relative_files() {
+    let fs = MemoryFileSystem::new().with_file("lib/io.rasm", IO).with_file("/abs.rasm", IO);
+
+    assert!(fs.exists("."));
+    assert!(fs.exists("./"));
+    assert!(fs.exists(""));
+    assert_eq!(fs.files(".").unwrap(), vec!["lib/io.rasm"]);
+}
@@ -0,0 +1,94 @@
+// every instruction has to assemble to the bytes the `rainbow_wrapper` macro for it gives,
+// with the operands in the order spec.md lists them
+use rainbow_wrapper::{*, types::{Type, Value}, wrapper::Wrapper};
+use common::*;
+
+mod common;
+
+// declares the variable `$x` the instructions below use
+const VAR_X: &str = "VAR i32 x\n";
+
+fn with_x(src: &str) -> String {
+    return VAR_X.to_string() + src;
+}
+
+// a program with the declaration of `$x` followed by `bytes`
+fn program(bytes: Vec<u8>) -> Vec<u8> {
+    let mut wrapper = Wrapper::new();
+    wrapper.push([var!(Value::TYPE(vec![Type::I32]), name!("x")), bytes].concat());
+
+    return wrapper.emit();
+}
+
+fn x() -> Value {
+    ident!("x")
+}
+
+fn imm(n: u64) -> Value {
+    immediate!(UNSIGNED(n))
+}
+
+macro_rules! golden {
+    ($name:ident, $src:expr, $expected:expr) => {
+        #[test]
+        fn $name() {
+            assert_eq!(assemble(&with_x($src)), program($expected));
+        }
+    };
+}
+
+golden!(nop, "NOP", vec![0x00]);
+golden!(push, "PUSH 1", push!(imm(1)));
+golden!(pop, "POP $x", pop!(x()));
+golden!(peek, "PEEK 1 $x", peek!(imm(1), x()));
+// `foo` and `Foo` have to be defined, after the instruction so the expected bytes start with it
+golden!(call, "CALL foo\nvoid foo() { }", [call!(name!("foo")), generation::generate_function(&"foo".to_string(), &vec![], &vec![Type::VOID], &vec![])].concat());
+golden!(add, "ADD 1 2 $x", add!(imm(1), imm(2), x()));
+golden!(sub, "SUB 1 2 $x", sub!(imm(1), imm(2), x()));
+golden!(mul, "MUL 1 2 $x", mul!(imm(1), imm(2), x()));
+golden!(div, "DIV 1 2 $x", div!(imm(1), imm(2), x()));
+golden!(jmp, "JMP 0", jmp!(imm(0)));
+golden!(jne, "JNE 0 1 2", jne!(imm(0), imm(1), imm(2)));
+golden!(je, "JE 0 1 2", je!(imm(0), imm(1), imm(2)));
+golden!(jge, "JGE 0 1 2", jge!(imm(0), imm(1), imm(2)));
+golden!(jg, "JG 0 1 2", jg!(imm(0), imm(1), imm(2)));
+golden!(jle, "JLE 0 1 2", jle!(imm(0), imm(1), imm(2)));
+golden!(jl, "JL 0 1 2", jl!(imm(0), imm(1), imm(2)));
+golden!(mov, "MOV 1 $x", mov!(imm(1), x()));
+golden!(and, "AND 1 2 $x", and!(imm(1), imm(2), x()));
+golden!(or, "OR 1 2 $x", or!(imm(1), imm(2), x()));
+golden!(xor, "XOR 1 2 $x", xor!(imm(1), imm(2), x()));
+golden!(not, "NOT 1 $x", not!(imm(1), x()));
+golden!(lsh, "LSH 1 2 $x", lsh!(imm(1), imm(2), x()));
+golden!(rsh, "RSH 1 2 $x", rsh!(imm(1), imm(2), x()));
+golden!(var, "VAR u8 y", var!(Value::TYPE(vec![Type::U8]), name!("y")));
+golden!(ret, "RET", ret!());
+golden!(ret_value, "RET $x", ret!(x()));
+golden!(deref, "DEREF $x $x", rainbow_wrapper::deref!(x(), x()));
+golden!(reference, "REF $x $x", r#ref!(x(), x()));
+golden!(inst, "INST Foo $x\n.struct Foo { i32 a }", [inst!(name!("Foo"), x()), generation::generate_struct(r#struct::Struct { name: "Foo".to_string(), types: vec![vec![Type::I32]], names: vec!["a".to_string()] })].concat());
+golden!(modulo, "MOD 1 2 $x", r#mod!(imm(1), imm(2), x()));
+golden!(pmov, "PMOV 1 $x 2", pmov!(imm(1), x(), imm(2)));
+golden!(alloc, "ALLOC i32 4 $x", alloc!(Value::TYPE(vec![Type::I32]), imm(4), x()));
+golden!(free, "FREE $x", free!(x()));
+golden!(free_size, "FREE $x 4", free!(x(), imm(4)));
+golden!(callc, "CALLC 1 i32 2", callc!(imm(1), Value::TYPE(vec![Type::I32]), imm(2)));
+golden!(cmp, "CMP >= 1 2 $x", cmp!(Value::UNSIGNED(2), imm(1), imm(2), x()));
+
+#[test]
+fn not_takes_two_operands() {
+    assert_eq!(assemble_err(&with_x("NOT 1 2 $x")), "NOT expects 2 operands, got 3");
+}
+
+#[test]
+fn operand_count_is_checked() {
+    assert_eq!(assemble_err(&with_x("ADD 1 2")), "ADD expects 3 operands, got 2");
+    assert_eq!(assemble_err(&with_x("RET 1 2")), "RET expects at most 1 operand, got 2");
+    assert_eq!(assemble_err(&with_x("FREE")), "FREE expects 1 or 2 operands, got 0");
+}
+
+#[test]
+fn destinations_have_to_be_variables() {
+    assert_eq!(assemble_err(&with_x("ADD 1 2 3")), "operand 3 of ADD is written to, so it has to be a variable, got a number");
+    assert_eq!(assemble_err(&with_x("POP x")), "operand 1 of POP is written to, so it has to be a variable, got a name");
+}
@@ -0,0 +1,52 @@
+// macros are expanded before parsing, into the code they would be if written out
+use common::*;
+use rasm::MemoryFileSystem;
+
+mod common;
+
+#[test]
+fn parameters_are_replaced_with_the_arguments() {
+    let src = ".macro ADD_TO a n {\n    ADD $a n $a\n    PUSH n\n}\nVAR i32 x\nADD_TO x 5";
+
+    assert_eq!(assemble(src), assemble("VAR i32 x\nADD $x 5 $x\nPUSH 5"));
+}
+
+#[test]
+fn labels_are_renamed_for_every_expansion() {
+    let src = ".macro COUNT n {\n    :loop\n    ADD $n 1 $n\n    JL $n 10 :loop\n}\nvoid f() {\n    VAR i32 x\n    COUNT x\n    COUNT x\n}";
+    let expanded = "void f() {\n    VAR i32 x\n    :a\n    ADD $x 1 $x\n    JL $x 10 :a\n    :b\n    ADD $x 1 $x\n    JL $x 10 :b\n}";
+
+    assert_eq!(assemble(src), assemble(expanded));
+}
+
+#[test]
+fn recursion_is_an_error() {
+    assert_eq!(assemble_err(".macro LOOP {\n    LOOP\n}\nLOOP"), "macro `LOOP` exceeded the recursion limit of 64");
+}
+
+#[test]
+fn exponential_expansion_is_an_error() {
+    // every macro uses the next one twice, 2^30 lines if nothing stopped it
+    let mut src = String::from(".macro M30 {\n    NOP\n}\n");
+    for i in 0..30 {
+        src += &format!(".macro M{i} {{\n    M{0}\n    M{0}\n}}\n", i + 1);
+    }
+    src += "M0";
+
+    assert!(assemble_err(&src).ends_with("expanded to more than 100000 lines"));
+}
+
+#[test]
+fn macros_can_be_written_on_one_line() {
+    assert_eq!(assemble(".macro ONE a { PUSH a }\nONE 1"), assemble("PUSH 1"));
+    assert_eq!(assemble(".macro UP n { :top ADD $n 1 $n JL $n 10 :top } VAR i32 x UP x UP x"), assemble("VAR i32 x :a ADD $x 1 $x JL $x 10 :a :b ADD $x 1 $x JL $x 10 :b"));
+}
+
+#[test]
+fn errors_in_an_expansion_point_at_the_call() {
+    let src = ".macro BAD {\n    ADD 1 2\n}\nvoid f() {\n    NOP\n    BAD\n}";
+
+    let error = &diagnostics(MemoryFileSystem::new(), src)[0];
+    assert_eq!(error.message, "ADD expects 3 operands, got 2");
+    assert_eq!((error.line, error.col_start, error.col_end), (6, 4, 7));
+}
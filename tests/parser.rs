@@ -0,0 +1,58 @@
+// the parser works on the tokens of the whole file, so where the lines break does not change the output
+use common::*;
+use rasm::{ast::{Item, Stmt}, Assembler, MemoryFileSystem};
+
+mod common;
+
+#[test]
+fn functions_can_be_written_on_one_line() {
+    let one_line = "void f(i32 x) { NOP ADD 1 2 $x }";
+    let lines = "void f(i32 x) {\n    NOP\n    ADD 1 2 $x\n}\n";
+
+    assert_eq!(assemble(one_line), assemble(lines));
+}
+
+#[test]
+fn blocks_can_close_after_code() {
+    let one_line = ".module m { void f() { { NOP } RET } }\n.struct S { i32 a i32 b }";
+    let lines = ".module m {\n    void f() {\n        {\n            NOP\n        }\n        RET\n    }\n}\n.struct S {\n    i32 a\n    i32 b\n}\n";
+
+    assert_eq!(assemble(one_line), assemble(lines));
+}
+
+#[test]
+fn labels_count_the_statements_before_them() {
+    assert_eq!(assemble("void f() { NOP { NOP } :end JMP :end }"), assemble("void f() { NOP { NOP } JMP 2 }"));
+}
+
+#[test]
+fn labels_on_the_next_line_are_not_operands() {
+    assert_eq!(assemble("void f() {\n    RET\n    :done\n    JMP :done\n}\n"), assemble("void f() { RET JMP 1 }"));
+    assert_eq!(assemble(".macro J l {\n    JMP l\n}\nvoid f() {\n    J :end\n    :end\n}\n"), assemble("void f() { JMP 1 }"));
+}
+
+#[test]
+fn defines_and_macros_work_anywhere_on_a_line() {
+    let src = ".define A 2\n.macro TWICE n {\n    PUSH n\n    PUSH n }\nvoid f(i32 x) { ADD A 1 $x NOP TWICE A RET }";
+
+    assert_eq!(assemble(src), assemble("void f(i32 x) { ADD 2 1 $x NOP PUSH 2 PUSH 2 RET }"));
+}
+
+#[test]
+fn parses_into_a_tree() {
+    let program = Assembler::new().file_system(MemoryFileSystem::new()).parse_str("void f() { :top JMP :top }").unwrap();
+
+    let function = match &program.items[..] {
+        [Item::FUNCTION(function)] => function,
+        items => panic!("expected a single function, got {items:?}"),
+    };
+
+    assert_eq!(function.name.name, "f");
+    assert!(matches!(&function.body[..], [Stmt::LABEL(label), Stmt::INSTR(instr)] if label.name == "top" && instr.operands.len() == 1));
+}
+
+#[test]
+fn unmatched_braces_are_errors() {
+    assert_eq!(assemble_err("NOP }"), "unexpected `}`");
+    assert_eq!(assemble_err("void f() { NOP"), "unclosed `{`");
+}
@@ -0,0 +1,26 @@
+// string and character literals, with escape sequences and raw strings
+use common::*;
+
+mod common;
+
+#[test]
+fn escapes_are_decoded() {
+    assert_eq!(assemble("PUSH \"\\x41\\u{42}\\t\""), assemble("PUSH \"AB\t\""));
+    assert_eq!(assemble("PUSH '\\x41'"), assemble("PUSH 65"));
+}
+
+#[test]
+fn hex_escapes_only_take_hex_digits() {
+    assert_eq!(assemble_err("PUSH \"\\x+1\""), "invalid escape sequence `\\x+1`, expected two hex digits");
+    assert_eq!(assemble_err("PUSH \"\\u{+41}\""), "invalid unicode escape `\\u{+41}`");
+}
+
+#[test]
+fn unicode_escapes_have_to_be_closed() {
+    assert_eq!(assemble_err("PUSH \"\\u{41\""), "unterminated unicode escape `\\u{41`, expected `}`");
+}
+
+#[test]
+fn raw_strings_keep_the_type_before_them() {
+    assert_eq!(assemble("PUSH s\n.data\ns char* r\"a\\b\""), assemble("PUSH \"a\\\\b\""));
+}
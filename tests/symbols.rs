@@ -0,0 +1,65 @@
+// every variable, function and struct that is used has to be defined, and nothing can be defined twice
+use common::*;
+use rasm::{Assembler, MemoryFileSystem};
+
+mod common;
+
+#[test]
+fn names_can_be_used_before_they_are_defined() {
+    let src = "void main() { CALL m.f INST S $s }\n.module m { void f() { CALL g } }\nvoid g() { }\n.struct S { i32 a }\nVAR STRUCT S s";
+
+    assert!(Assembler::new().file_system(MemoryFileSystem::new()).assemble_str(src).is_ok());
+}
+
+#[test]
+fn undefined_names_suggest_similar_ones() {
+    let errors = diagnostics(MemoryFileSystem::new(), "void print() { VAR i32 count PUSH $cuont CALL prnt }");
+
+    assert_eq!(errors[0].message, "undefined variable `$cuont`");
+    assert_eq!(errors[0].help.as_deref(), Some("did you mean `$count`?"));
+    assert_eq!(errors[1].message, "undefined function `prnt`");
+    assert_eq!(errors[1].help.as_deref(), Some("did you mean `print`?"));
+}
+
+#[test]
+fn variables_are_declared_per_block() {
+    assert_eq!(diagnostics(MemoryFileSystem::new(), "void f() { PUSH $x VAR i32 x }")[0].message, "undefined variable `$x`");
+    assert_eq!(diagnostics(MemoryFileSystem::new(), "void f() { { VAR i32 x } PUSH $x }")[0].message, "undefined variable `$x`");
+    assert_eq!(diagnostics(MemoryFileSystem::new(), "void f() { VAR i32 x VAR i32 x }")[0].message, "variable `x` is already declared in this block");
+
+    // variables outside of functions are declared before the functions using them, but only reported once
+    let errors = diagnostics(MemoryFileSystem::new(), "VAR i32 x\nVAR i32 x");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "variable `x` is already declared in this block");
+}
+
+#[test]
+fn if_branches_declare_in_the_enclosing_block() {
+    let src = ".runtime OS\nvoid f() {\n.if OS == 1\nVAR i32 x\n.end\nPUSH $x\n}";
+    assert!(Assembler::new().file_system(MemoryFileSystem::new()).assemble_str(src).is_ok());
+
+    let src = ".runtime OS\nvoid f() {\nVAR i32 x\n.if OS == 1\nVAR i32 x\n.end\n}";
+    assert_eq!(diagnostics(MemoryFileSystem::new(), src)[0].message, "variable `x` is already declared in this block");
+}
+
+#[test]
+fn duplicate_definitions_are_errors() {
+    let errors = diagnostics(MemoryFileSystem::new(), "void f() { }\n.struct f { i32 a i32 a }");
+
+    assert_eq!(errors[0].message, "`f` is already defined");
+    assert_eq!(errors[0].help.as_deref(), Some("it was first defined as a function on line 1"));
+    assert_eq!(errors[1].message, "duplicate field `a` in struct `f`");
+}
+
+#[test]
+fn included_files_declare_their_names() {
+    let fs = MemoryFileSystem::new().with_file("lib.rasm", ".module fs { void open() { } }");
+    let errors = diagnostics(fs, ".include \"lib.rasm\"\nvoid main() { CALL lib.fs.open CALL lib.fs.close }");
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "undefined function `lib.fs.close`");
+
+    // without a `.decl` next to the `.rbb` nothing is known about it
+    let fs = MemoryFileSystem::new().with_file("x.rbb", "");
+    assert!(Assembler::new().file_system(fs).assemble_str(".include x\nvoid main() { CALL x.anything }").is_ok());
+}
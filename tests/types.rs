@@ -0,0 +1,54 @@
+// the type checker warns about operands whose types do not fit together, `strict` makes that an error
+use common::*;
+use rasm::{Assembler, MemoryFileSystem, Severity};
+
+mod common;
+
+#[test]
+fn matching_types_do_not_warn() {
+    assert_eq!(warnings("i16 f(i16 a, i16* p) { ADD $a 1 $a DEREF $p $a MOV 2 $a RET $a }"), Vec::<String>::new());
+}
+
+#[test]
+fn mismatched_operands_warn() {
+    let src = "void f() { VAR i16 a VAR i16* ptr MOV 3.5 $a ADD $ptr 1 $a FREE $a }";
+
+    assert_eq!(warnings(src), vec![
+        "moving the decimal `3.5` into `$a`, which is a `i16`",
+        "mismatched types in `ADD`, `$ptr` is a `i16*` but `$a` is a `i16`",
+        "`FREE` needs a pointer, but `$a` is a `i16`",
+    ]);
+}
+
+#[test]
+fn struct_fields_and_return_types_are_checked() {
+    let src = ".struct P { f32 x }\ni32 f() { VAR STRUCT P p VAR i32 n MOV $p.x $n RET }";
+
+    assert_eq!(warnings(src), vec![
+        "moving the `f32` `$p.x` into `$n`, which is a `i32`",
+        "`f` returns `i32`, but `RET` has no value",
+    ]);
+}
+
+#[test]
+fn variables_from_if_branches_keep_their_type() {
+    let src = ".runtime OS\nvoid f() {\n.if OS == 1\nVAR i16 a\n.end\nMOV 3.5 $a\n}";
+
+    assert_eq!(warnings(src), vec!["moving the decimal `3.5` into `$a`, which is a `i16`"]);
+}
+
+#[test]
+fn constants_are_not_mistaken_for_variables() {
+    let src = "void f() { VAR i32 n VAR f32 c MOV c $n }\n.data\nc i32 5";
+
+    assert_eq!(warnings(src), Vec::<String>::new());
+}
+
+#[test]
+fn strict_makes_warnings_errors() {
+    let res = Assembler::new().file_system(MemoryFileSystem::new()).strict(true).assemble_str("void f() { VAR i8 a MOV 300 $a }");
+
+    let errors = res.unwrap_err().errors;
+    assert_eq!(errors[0].message, "`300` does not fit in `$a`, which is a `i8`");
+    assert_eq!(errors[0].severity, Severity::ERROR);
+}